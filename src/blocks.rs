@@ -0,0 +1,148 @@
+//! Index of the command blocks of a terminal, built from semantic prompt
+//! marks. Positions are absolute rows and columns in the VTE buffer.
+
+use crate::osc::Mark;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Older blocks are dropped once this many are indexed.
+const MAX_BLOCKS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub row: i64,
+    pub column: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Start of the prompt.
+    pub prompt: Position,
+
+    /// Start of the command line typed by the user.
+    pub input: Option<Position>,
+
+    /// Start of the command output.
+    pub output: Option<Position>,
+
+    /// End of the command output.
+    pub end: Option<Position>,
+
+    pub exit_code: Option<i32>,
+
+    /// The command line typed by the user.
+    pub command: Option<String>,
+
+    pub started: Option<Instant>,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+pub struct Blocks {
+    blocks: VecDeque<Block>,
+}
+
+impl Block {
+    fn new(prompt: Position) -> Self {
+        Self {
+            prompt,
+            input: None,
+            output: None,
+            end: None,
+            exit_code: None,
+            command: None,
+            started: None,
+            duration: None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.end.is_some()
+    }
+}
+
+impl Blocks {
+    pub fn mark(&mut self, mark: Mark, position: Position) {
+        tracing::debug!("Mark `{mark:?}` at {position:?}.");
+        match mark {
+            Mark::PromptStart => {
+                // Close a block whose command did not report its end.
+                if let Some(block) = self.blocks.back_mut() {
+                    if block.output.is_some() && block.end.is_none() {
+                        block.end = Some(position);
+                    }
+                }
+                if self.blocks.len() == MAX_BLOCKS {
+                    self.blocks.pop_front();
+                }
+                self.blocks.push_back(Block::new(position));
+            }
+            Mark::CommandStart => {
+                if let Some(block) = self.blocks.back_mut() {
+                    block.input = Some(position);
+                }
+            }
+            Mark::CommandExecuted => {
+                if let Some(block) = self.blocks.back_mut() {
+                    block.output = Some(position);
+                    block.started = Some(Instant::now());
+                }
+            }
+            Mark::CommandFinished(exit_code) => {
+                if let Some(block) = self.blocks.back_mut() {
+                    if block.output.is_some() && block.end.is_none() {
+                        block.end = Some(position);
+                        block.exit_code = exit_code;
+                        block.duration = block.started.map(|started| started.elapsed());
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter()
+    }
+
+    pub fn last(&self) -> Option<&Block> {
+        self.blocks.back()
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut Block> {
+        self.blocks.back_mut()
+    }
+
+    /// The row of the closest prompt above `row`.
+    pub fn previous_prompt(&self, row: i64) -> Option<i64> {
+        self.blocks
+            .iter()
+            .rev()
+            .map(|block| block.prompt.row)
+            .find(|prompt| *prompt < row)
+    }
+
+    /// The row of the closest prompt below `row`.
+    pub fn next_prompt(&self, row: i64) -> Option<i64> {
+        self.blocks
+            .iter()
+            .map(|block| block.prompt.row)
+            .find(|prompt| *prompt > row)
+    }
+
+    /// Forget the blocks whose prompt is above `row`, e.g. because they were
+    /// cleared from the scrollback.
+    pub fn truncate_before(&mut self, row: i64) {
+        self.blocks.retain(|block| block.prompt.row >= row);
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// The most recent block that has finished running.
+    pub fn last_finished(&self) -> Option<&Block> {
+        self.blocks.iter().rev().find(|block| block.is_finished())
+    }
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bell {
+    #[serde(default = "Bell::default_mode")]
+    pub mode: BellMode,
+
+    #[serde(default = "Bell::default_interval")]
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BellMode {
+    None,
+    Audible,
+    Visual,
+    Urgent,
+    Notification,
+}
+
+impl Bell {
+    fn default_mode() -> BellMode {
+        BellMode::None
+    }
+
+    fn default_interval() -> u64 {
+        500
+    }
+}
+
+impl Default for Bell {
+    fn default() -> Self {
+        Self {
+            mode: Self::default_mode(),
+            interval: Self::default_interval(),
+        }
+    }
+}
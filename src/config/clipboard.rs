@@ -0,0 +1,129 @@
+use crate::osc::MAX_CLIPBOARD_SIZE;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Clipboard {
+    #[serde(default = "Clipboard::default_confirm_paste")]
+    pub confirm_paste: bool,
+
+    #[serde(default = "Clipboard::default_strip_trailing_newline")]
+    pub strip_trailing_newline: bool,
+
+    #[serde(default = "Clipboard::default_convert_tabs")]
+    pub convert_tabs: Option<u32>,
+
+    #[serde(default = "Clipboard::default_osc52")]
+    pub osc52: Osc52,
+
+    #[serde(default = "Clipboard::default_copy_on_select")]
+    pub copy_on_select: bool,
+
+    #[serde(default = "Clipboard::default_middle_click_paste")]
+    pub middle_click_paste: bool,
+
+    #[serde(default = "Clipboard::default_trim_trailing_whitespace")]
+    pub trim_trailing_whitespace: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Osc52 {
+    #[serde(default = "Osc52::default_read")]
+    pub read: Permission,
+
+    #[serde(default = "Osc52::default_write")]
+    pub write: Permission,
+
+    #[serde(default = "Osc52::default_max_size", deserialize_with = "max_size")]
+    pub max_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Allow,
+    Ask,
+    Deny,
+}
+
+impl Clipboard {
+    fn default_confirm_paste() -> bool {
+        true
+    }
+
+    fn default_strip_trailing_newline() -> bool {
+        false
+    }
+
+    fn default_convert_tabs() -> Option<u32> {
+        None
+    }
+
+    fn default_osc52() -> Osc52 {
+        Osc52::default()
+    }
+
+    fn default_copy_on_select() -> bool {
+        false
+    }
+
+    fn default_middle_click_paste() -> bool {
+        true
+    }
+
+    fn default_trim_trailing_whitespace() -> bool {
+        true
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self {
+            confirm_paste: Self::default_confirm_paste(),
+            strip_trailing_newline: Self::default_strip_trailing_newline(),
+            convert_tabs: Self::default_convert_tabs(),
+            osc52: Self::default_osc52(),
+            copy_on_select: Self::default_copy_on_select(),
+            middle_click_paste: Self::default_middle_click_paste(),
+            trim_trailing_whitespace: Self::default_trim_trailing_whitespace(),
+        }
+    }
+}
+
+impl Osc52 {
+    fn default_read() -> Permission {
+        Permission::Ask
+    }
+
+    fn default_write() -> Permission {
+        Permission::Allow
+    }
+
+    fn default_max_size() -> usize {
+        512 * 1024
+    }
+}
+
+fn max_size<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let max_size = usize::deserialize(deserializer)?;
+    if max_size > MAX_CLIPBOARD_SIZE {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid maximum size {max_size}. Expected at most {MAX_CLIPBOARD_SIZE} bytes."
+        )));
+    }
+    Ok(max_size)
+}
+
+impl Default for Osc52 {
+    fn default() -> Self {
+        Self {
+            read: Self::default_read(),
+            write: Self::default_write(),
+            max_size: Self::default_max_size(),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Close {
+    #[serde(default = "Close::default_confirm")]
+    pub confirm: bool,
+
+    #[serde(default = "Close::default_ignore")]
+    pub ignore: Vec<String>,
+}
+
+impl Close {
+    fn default_confirm() -> bool {
+        true
+    }
+
+    fn default_ignore() -> Vec<String> {
+        [
+            "bash", "zsh", "fish", "sh", "dash", "ksh", "tcsh", "nu", "less", "more", "man",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+}
+
+impl Default for Close {
+    fn default() -> Self {
+        Self {
+            confirm: Self::default_confirm(),
+            ignore: Self::default_ignore(),
+        }
+    }
+}
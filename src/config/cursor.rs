@@ -0,0 +1,85 @@
+use crate::theme::deserialize;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cursor {
+    #[serde(default = "Cursor::default_shape")]
+    pub shape: CursorShape,
+
+    #[serde(default = "Cursor::default_blink")]
+    pub blink: CursorBlink,
+
+    #[serde(default = "Cursor::default_blink_timeout")]
+    pub blink_timeout: Option<u32>,
+
+    #[serde(default = "Cursor::default_application_shape")]
+    pub application_shape: bool,
+
+    #[serde(
+        default = "Cursor::default_color",
+        deserialize_with = "deserialize::optional_color"
+    )]
+    pub color: Option<String>,
+
+    #[serde(
+        default = "Cursor::default_text_color",
+        deserialize_with = "deserialize::optional_color"
+    )]
+    pub text_color: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorShape {
+    Block,
+    Ibeam,
+    Underline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorBlink {
+    System,
+    On,
+    Off,
+}
+
+impl Cursor {
+    fn default_shape() -> CursorShape {
+        CursorShape::Block
+    }
+
+    fn default_blink() -> CursorBlink {
+        CursorBlink::System
+    }
+
+    fn default_blink_timeout() -> Option<u32> {
+        None
+    }
+
+    fn default_application_shape() -> bool {
+        true
+    }
+
+    fn default_color() -> Option<String> {
+        None
+    }
+
+    fn default_text_color() -> Option<String> {
+        None
+    }
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            shape: Self::default_shape(),
+            blink: Self::default_blink(),
+            blink_timeout: Self::default_blink_timeout(),
+            application_shape: Self::default_application_shape(),
+            color: Self::default_color(),
+            text_color: Self::default_text_color(),
+        }
+    }
+}
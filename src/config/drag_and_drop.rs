@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DragAndDrop {
+    #[serde(default = "DragAndDrop::default_quoting")]
+    pub quoting: Quoting,
+
+    #[serde(default = "DragAndDrop::default_cd_into_directory")]
+    pub cd_into_directory: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quoting {
+    Single,
+    Double,
+    Backslash,
+}
+
+impl DragAndDrop {
+    fn default_quoting() -> Quoting {
+        Quoting::Single
+    }
+
+    fn default_cd_into_directory() -> bool {
+        false
+    }
+}
+
+impl Default for DragAndDrop {
+    fn default() -> Self {
+        Self {
+            quoting: Self::default_quoting(),
+            cd_into_directory: Self::default_cd_into_directory(),
+        }
+    }
+}
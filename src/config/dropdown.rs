@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dropdown {
+    #[serde(default = "Dropdown::default_height")]
+    pub height: u32,
+
+    #[serde(default = "Dropdown::default_hide_on_focus_loss")]
+    pub hide_on_focus_loss: bool,
+}
+
+impl Dropdown {
+    fn default_height() -> u32 {
+        40
+    }
+
+    fn default_hide_on_focus_loss() -> bool {
+        true
+    }
+}
+
+impl Default for Dropdown {
+    fn default() -> Self {
+        Self {
+            height: Self::default_height(),
+            hide_on_focus_loss: Self::default_hide_on_focus_loss(),
+        }
+    }
+}
@@ -11,6 +11,18 @@ pub struct General {
 
     #[serde(default = "General::default_theme")]
     pub theme: Option<String>,
+
+    #[serde(default = "General::default_on_exit")]
+    pub on_exit: OnExit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnExit {
+    Close,
+    Hold,
+    HoldOnError,
+    Restart,
 }
 
 impl General {
@@ -25,6 +37,10 @@ impl General {
     fn default_theme() -> Option<String> {
         None
     }
+
+    fn default_on_exit() -> OnExit {
+        OnExit::Close
+    }
 }
 
 impl Default for General {
@@ -33,6 +49,7 @@ impl Default for General {
             command: Self::default_command(),
             font: Self::default_font(),
             theme: Self::default_theme(),
+            on_exit: Self::default_on_exit(),
         }
     }
 }
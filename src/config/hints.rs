@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hints {
+    #[serde(default = "Hints::default_alphabet", deserialize_with = "alphabet")]
+    pub alphabet: String,
+
+    #[serde(default = "Hints::default_action")]
+    pub action: HintAction,
+
+    #[serde(default = "Hints::default_patterns")]
+    pub patterns: Vec<HintPattern>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HintPattern {
+    pub regex: String,
+
+    #[serde(default)]
+    pub action: Option<HintAction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HintAction {
+    Copy,
+    Open,
+    Insert,
+}
+
+impl Hints {
+    fn default_alphabet() -> String {
+        "asdfghjklqwertyuiopzxcvbnm".to_string()
+    }
+
+    fn default_action() -> HintAction {
+        HintAction::Copy
+    }
+
+    fn default_patterns() -> Vec<HintPattern> {
+        Vec::new()
+    }
+}
+
+/// Labels need at least two characters to tell hints apart, and upper case
+/// characters are left for inserting the picked match.
+fn alphabet<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let alphabet = String::deserialize(deserializer)?;
+    let characters: Vec<char> = alphabet.chars().collect();
+    if characters.len() < 2 {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid alphabet {alphabet:?}. Expected at least 2 characters."
+        )));
+    }
+    if let Some(c) = characters.iter().find(|c| c.is_uppercase()) {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid alphabet {alphabet:?}. Expected no upper case characters but found {c:?}."
+        )));
+    }
+    let duplicate = characters
+        .iter()
+        .enumerate()
+        .find_map(|(index, c)| characters[..index].contains(c).then_some(c));
+    if let Some(c) = duplicate {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid alphabet {alphabet:?}. Expected no duplicate characters but found {c:?} twice."
+        )));
+    }
+    Ok(alphabet)
+}
+
+impl Default for Hints {
+    fn default() -> Self {
+        Self {
+            alphabet: Self::default_alphabet(),
+            action: Self::default_action(),
+            patterns: Self::default_patterns(),
+        }
+    }
+}
@@ -1,9 +1,27 @@
-use self::{error::Result, general::General, window::Window};
+use self::{
+    bell::Bell, clipboard::Clipboard, close::Close, cursor::Cursor, drag_and_drop::DragAndDrop,
+    dropdown::Dropdown, error::Result, general::General, hints::Hints,
+    notifications::Notifications, recording::Recording, restart::Restart, scrollback::Scrollback,
+    session::Session, shortcuts::Shortcuts, window::Window,
+};
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, path::Path};
 
+pub mod bell;
+pub mod clipboard;
+pub mod close;
+pub mod cursor;
+pub mod drag_and_drop;
+pub mod dropdown;
 pub mod error;
 pub mod general;
+pub mod hints;
+pub mod notifications;
+pub mod recording;
+pub mod restart;
+pub mod scrollback;
+pub mod session;
+pub mod shortcuts;
 pub mod window;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +32,45 @@ pub struct Config {
 
     #[serde(default = "Config::default_window")]
     pub window: Window,
+
+    #[serde(default = "Config::default_notifications")]
+    pub notifications: Notifications,
+
+    #[serde(default = "Config::default_bell")]
+    pub bell: Bell,
+
+    #[serde(default = "Config::default_cursor")]
+    pub cursor: Cursor,
+
+    #[serde(default = "Config::default_scrollback")]
+    pub scrollback: Scrollback,
+
+    #[serde(default = "Config::default_recording")]
+    pub recording: Recording,
+
+    #[serde(default = "Config::default_dropdown")]
+    pub dropdown: Dropdown,
+
+    #[serde(default = "Config::default_session")]
+    pub session: Session,
+
+    #[serde(default = "Config::default_close")]
+    pub close: Close,
+
+    #[serde(default = "Config::default_restart")]
+    pub restart: Restart,
+
+    #[serde(default = "Config::default_clipboard")]
+    pub clipboard: Clipboard,
+
+    #[serde(default = "Config::default_shortcuts")]
+    pub shortcuts: Shortcuts,
+
+    #[serde(default = "Config::default_hints")]
+    pub hints: Hints,
+
+    #[serde(default = "Config::default_drag_and_drop")]
+    pub drag_and_drop: DragAndDrop,
 }
 
 impl Config {
@@ -33,6 +90,58 @@ impl Config {
     fn default_window() -> Window {
         Window::default()
     }
+
+    fn default_notifications() -> Notifications {
+        Notifications::default()
+    }
+
+    fn default_bell() -> Bell {
+        Bell::default()
+    }
+
+    fn default_cursor() -> Cursor {
+        Cursor::default()
+    }
+
+    fn default_scrollback() -> Scrollback {
+        Scrollback::default()
+    }
+
+    fn default_recording() -> Recording {
+        Recording::default()
+    }
+
+    fn default_dropdown() -> Dropdown {
+        Dropdown::default()
+    }
+
+    fn default_session() -> Session {
+        Session::default()
+    }
+
+    fn default_close() -> Close {
+        Close::default()
+    }
+
+    fn default_restart() -> Restart {
+        Restart::default()
+    }
+
+    fn default_clipboard() -> Clipboard {
+        Clipboard::default()
+    }
+
+    fn default_shortcuts() -> Shortcuts {
+        Shortcuts::default()
+    }
+
+    fn default_hints() -> Hints {
+        Hints::default()
+    }
+
+    fn default_drag_and_drop() -> DragAndDrop {
+        DragAndDrop::default()
+    }
 }
 
 impl Default for Config {
@@ -40,6 +149,19 @@ impl Default for Config {
         Self {
             general: Self::default_general(),
             window: Self::default_window(),
+            notifications: Self::default_notifications(),
+            bell: Self::default_bell(),
+            cursor: Self::default_cursor(),
+            scrollback: Self::default_scrollback(),
+            recording: Self::default_recording(),
+            dropdown: Self::default_dropdown(),
+            session: Self::default_session(),
+            close: Self::default_close(),
+            restart: Self::default_restart(),
+            clipboard: Self::default_clipboard(),
+            shortcuts: Self::default_shortcuts(),
+            hints: Self::default_hints(),
+            drag_and_drop: Self::default_drag_and_drop(),
         }
     }
 }
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Notifications {
+    #[serde(default = "Notifications::default_sequences")]
+    pub sequences: bool,
+
+    #[serde(default = "Notifications::default_long_command_seconds")]
+    pub long_command_seconds: Option<u64>,
+}
+
+impl Notifications {
+    fn default_sequences() -> bool {
+        true
+    }
+
+    fn default_long_command_seconds() -> Option<u64> {
+        None
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            sequences: Self::default_sequences(),
+            long_command_seconds: Self::default_long_command_seconds(),
+        }
+    }
+}
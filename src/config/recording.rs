@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recording {
+    #[serde(default = "Recording::default_input")]
+    pub input: bool,
+}
+
+impl Recording {
+    fn default_input() -> bool {
+        false
+    }
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self {
+            input: Self::default_input(),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Restart {
+    #[serde(default = "Restart::default_delay", deserialize_with = "finite")]
+    pub delay: f64,
+
+    #[serde(default = "Restart::default_max_attempts")]
+    pub max_attempts: u32,
+
+    #[serde(default = "Restart::default_backoff", deserialize_with = "finite")]
+    pub backoff: f64,
+}
+
+impl Restart {
+    fn default_delay() -> f64 {
+        1.0
+    }
+
+    fn default_max_attempts() -> u32 {
+        5
+    }
+
+    fn default_backoff() -> f64 {
+        2.0
+    }
+}
+
+fn finite<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = f64::deserialize(deserializer)?;
+    if !value.is_finite() {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid number {value}. Expected a finite number."
+        )));
+    }
+    Ok(value)
+}
+
+impl Default for Restart {
+    fn default() -> Self {
+        Self {
+            delay: Self::default_delay(),
+            max_attempts: Self::default_max_attempts(),
+            backoff: Self::default_backoff(),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scrollback {
+    #[serde(default = "Scrollback::default_lines")]
+    pub lines: ScrollbackLines,
+
+    #[serde(default = "Scrollback::default_on_output")]
+    pub on_output: bool,
+
+    #[serde(default = "Scrollback::default_on_keystroke")]
+    pub on_keystroke: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScrollbackLines {
+    Limited(u32),
+    Unlimited(Unlimited),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unlimited {
+    Unlimited,
+}
+
+impl Scrollback {
+    fn default_lines() -> ScrollbackLines {
+        ScrollbackLines::Limited(10000)
+    }
+
+    fn default_on_output() -> bool {
+        false
+    }
+
+    fn default_on_keystroke() -> bool {
+        true
+    }
+}
+
+impl Default for Scrollback {
+    fn default() -> Self {
+        Self {
+            lines: Self::default_lines(),
+            on_output: Self::default_on_output(),
+            on_keystroke: Self::default_on_keystroke(),
+        }
+    }
+}
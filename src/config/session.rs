@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Session {
+    #[serde(default = "Session::default_remember_size")]
+    pub remember_size: bool,
+
+    #[serde(default = "Session::default_restore")]
+    pub restore: bool,
+}
+
+impl Session {
+    fn default_remember_size() -> bool {
+        true
+    }
+
+    fn default_restore() -> bool {
+        false
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            remember_size: Self::default_remember_size(),
+            restore: Self::default_restore(),
+        }
+    }
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Shortcuts replacing the default ones of actions, by action name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Shortcuts(pub HashMap<String, Vec<String>>);
@@ -14,6 +14,28 @@ pub struct Window {
 
     #[serde(default = "Window::default_padding")]
     pub padding: Padding,
+
+    #[serde(default = "Window::default_start_mode")]
+    pub start_mode: StartMode,
+
+    #[serde(default = "Window::default_header_bar")]
+    pub header_bar: HeaderBar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartMode {
+    Normal,
+    Maximized,
+    Fullscreen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HeaderBar {
+    Always,
+    Never,
+    AutoHide,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +74,14 @@ impl Window {
     fn default_padding() -> Padding {
         Padding::default()
     }
+
+    fn default_start_mode() -> StartMode {
+        StartMode::Normal
+    }
+
+    fn default_header_bar() -> HeaderBar {
+        HeaderBar::Always
+    }
 }
 
 impl Default for Window {
@@ -61,6 +91,8 @@ impl Default for Window {
             scroll_bar: Self::default_scroll_bar(),
             size: Self::default_size(),
             padding: Self::default_padding(),
+            start_mode: Self::default_start_mode(),
+            header_bar: Self::default_header_bar(),
         }
     }
 }
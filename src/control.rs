@@ -0,0 +1,337 @@
+//! Control of running instances over a Unix socket.
+//!
+//! Every instance listens on a socket in the runtime directory of the user.
+//! Clients send one request as a line of JSON and receive one response as a
+//! line of JSON. Only the user owning the instance may connect.
+
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, DirBuilder},
+    io::{self, BufRead, BufReader, Read, Write},
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::{
+            fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+    },
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Environment variable with the socket of the instance a shell runs in.
+pub const SOCKET_VARIABLE: &str = "GALACTIC_SOCKET";
+
+/// Environment variable with the ID of the terminal a shell runs in.
+pub const TERMINAL_VARIABLE: &str = "GALACTIC_TERMINAL_ID";
+
+pub const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Longer requests are refused, so a client can not make the instance buffer
+/// without end.
+const MAX_REQUEST_LENGTH: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Subcommand, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    #[command(about = "Open a new window")]
+    NewWindow {
+        #[arg(long, help = "Start the shell in this directory")]
+        working_directory: Option<PathBuf>,
+    },
+
+    #[command(about = "Open a new tab, which is a new window for now")]
+    NewTab {
+        #[arg(long, help = "Start the shell in this directory")]
+        working_directory: Option<PathBuf>,
+    },
+
+    #[command(about = "Send text to a terminal as if it was typed")]
+    SendText {
+        #[arg(long, help = "The ID of the terminal")]
+        terminal: Option<u32>,
+
+        text: String,
+    },
+
+    #[command(about = "Set the title of the window of a terminal")]
+    SetTitle {
+        #[arg(long, help = "The ID of the terminal")]
+        terminal: Option<u32>,
+
+        #[arg(help = "The title, or an empty title to restore the configured one")]
+        title: String,
+    },
+
+    #[command(about = "List the terminals")]
+    List,
+
+    #[command(about = "Focus the window of a terminal")]
+    Focus {
+        #[arg(help = "The ID of the terminal")]
+        terminal: u32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Response {
+    Done,
+    Created { terminal: u32 },
+    Terminals { terminals: Vec<TerminalInfo> },
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalInfo {
+    pub id: u32,
+    pub title: String,
+    pub directory: Option<PathBuf>,
+    pub active: bool,
+}
+
+/// Listens for requests on the socket of this instance. The socket is
+/// removed when the server is dropped.
+#[derive(Debug)]
+pub struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+/// A connection accepted by [`Server::accept`]. Its request is read as it
+/// arrives, so a slow client does not block the instance.
+#[derive(Debug)]
+pub struct Connection {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+}
+
+impl Server {
+    pub fn bind() -> io::Result<Self> {
+        let path = socket_path();
+        if let Some(directory) = path.parent() {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(directory)?;
+            check_directory(directory)?;
+        }
+
+        // A socket left behind by a crashed instance with the same process
+        // ID would make binding fail.
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+
+        tracing::info!("Listen for control requests on {path:?}.");
+        Ok(Self { listener, path })
+    }
+
+    /// Accept a pending connection. Connections of other users are refused.
+    pub fn accept(&self) -> io::Result<Connection> {
+        let (stream, _) = self.listener.accept()?;
+        let uid = peer_uid(&stream)?;
+        if uid != unsafe { libc::getuid() } {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("Connection of user {uid} refused"),
+            ));
+        }
+
+        stream.set_nonblocking(true)?;
+        Ok(Connection {
+            stream,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl Connection {
+    /// Read what has arrived without blocking. The request is returned once
+    /// its line is complete.
+    pub fn read_request(&mut self) -> io::Result<Option<Request>> {
+        let mut chunk = [0; 4096];
+        loop {
+            match (&self.stream).read(&mut chunk) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(length) => self.buffer.extend_from_slice(&chunk[..length]),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+
+            if let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                return Ok(Some(serde_json::from_slice(&self.buffer[..end])?));
+            }
+            if self.buffer.len() > MAX_REQUEST_LENGTH {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Request too long",
+                ));
+            }
+        }
+    }
+
+    /// Write the response to the request. Responses are short, so this only
+    /// blocks if the client stops reading, and then not for long.
+    pub fn respond(&self, response: &Response) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        self.stream.set_write_timeout(Some(TIMEOUT))?;
+        write_line(&mut &self.stream, response)
+    }
+}
+
+impl AsRawFd for Connection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl AsRawFd for Server {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            tracing::warn!("Failed to remove socket {:?}: {error}.", self.path);
+        }
+    }
+}
+
+/// Send `request` to the instance listening on `socket`, falling back to the
+/// instance the current shell runs in and then to any running instance.
+pub fn send(socket: Option<&Path>, request: &Request) -> io::Result<Response> {
+    let mut stream = connect(socket)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    write_line(&mut stream, request)?;
+    read_line(&stream)
+}
+
+/// The socket of this instance.
+pub fn socket_path() -> PathBuf {
+    socket_directory().join(format!("{}.sock", std::process::id()))
+}
+
+fn socket_directory() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(directory) => directory.join("galactic"),
+        None => std::env::temp_dir().join(format!("galactic-{}", unsafe { libc::getuid() })),
+    }
+}
+
+fn connect(socket: Option<&Path>) -> io::Result<UnixStream> {
+    let socket = socket
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(SOCKET_VARIABLE).map(PathBuf::from));
+    if let Some(socket) = socket {
+        return UnixStream::connect(socket);
+    }
+
+    // Prefer the most recently started instance.
+    sockets()?
+        .iter()
+        .rev()
+        .find_map(|path| UnixStream::connect(path).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No running instance found"))
+}
+
+/// Whether an instance other than this one is running, which is the case if
+/// another socket accepts connections.
+pub fn other_instance_running() -> bool {
+    let own = socket_path();
+    match sockets() {
+        Ok(sockets) => sockets
+            .iter()
+            .filter(|path| **path != own)
+            .any(|path| UnixStream::connect(path).is_ok()),
+        Err(error) => {
+            tracing::debug!("Failed to look for running instances: {error}.");
+            false
+        }
+    }
+}
+
+/// The sockets in the socket directory, sorted by the time they were created.
+/// Sockets of instances that have crashed may be left behind.
+fn sockets() -> io::Result<Vec<PathBuf>> {
+    let directory = socket_directory();
+    check_directory(&directory)?;
+    let mut sockets: Vec<_> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == "sock")
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    sockets.sort();
+    Ok(sockets.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Make sure that only the user has access to the socket `directory`, which
+/// may be in the shared temporary directory.
+fn check_directory(directory: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(directory)?;
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Socket directory {directory:?} is not private to user {uid}"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut credentials as *mut libc::ucred).cast(),
+            &mut length,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(credentials.uid)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut uid = 0;
+    let mut gid = 0;
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+fn read_line<T: for<'a> Deserialize<'a>>(stream: &UnixStream) -> io::Result<T> {
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+fn write_line<T: Serialize>(stream: &mut impl Write, value: &T) -> io::Result<()> {
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
@@ -0,0 +1,648 @@
+//! Keyboard driven selection in the scrollback with vi keys. Positions are
+//! absolute rows and columns in the VTE buffer, columns are counted in
+//! characters.
+
+use crate::blocks::Position;
+
+/// The distance of the tab stops in cells, as VTE sets them on a reset.
+const TAB_WIDTH: i64 = 8;
+
+/// Access to the text of the terminal.
+pub trait Buffer {
+    /// The text of `row` without the line break.
+    fn line(&self, row: i64) -> String;
+
+    /// The first and the last row of the scrollback and the screen.
+    fn rows(&self) -> (i64, i64);
+
+    /// The first visible row and the number of visible rows.
+    fn screen(&self) -> (i64, i64);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKind {
+    Character,
+    Line,
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Char(char),
+    Ctrl(char),
+    Escape,
+    Enter,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Continue,
+    Yank(String),
+    Exit,
+}
+
+#[derive(Debug, Clone)]
+pub struct CopyMode {
+    pub cursor: Position,
+    anchor: Option<(Position, SelectionKind)>,
+
+    /// The search query while it is typed.
+    query: Option<(String, bool)>,
+    last_search: Option<(String, bool)>,
+
+    /// Whether `g` was pressed, waiting for the second `g` of `gg`.
+    pending_g: bool,
+}
+
+impl CopyMode {
+    pub fn new(cursor: Position) -> Self {
+        Self {
+            cursor,
+            anchor: None,
+            query: None,
+            last_search: None,
+            pending_g: false,
+        }
+    }
+
+    /// Start with `anchor` to `cursor` selected.
+    pub fn with_selection(anchor: Position, cursor: Position, kind: SelectionKind) -> Self {
+        Self {
+            anchor: Some((anchor, kind)),
+            ..Self::new(cursor)
+        }
+    }
+
+    /// The selection as start and end position with the kind of selection.
+    /// Both positions are included.
+    pub fn selection(&self) -> Option<(Position, Position, SelectionKind)> {
+        let (anchor, kind) = self.anchor?;
+        let (start, end) = match kind {
+            SelectionKind::Block => (
+                Position {
+                    row: anchor.row.min(self.cursor.row),
+                    column: anchor.column.min(self.cursor.column),
+                },
+                Position {
+                    row: anchor.row.max(self.cursor.row),
+                    column: anchor.column.max(self.cursor.column),
+                },
+            ),
+            _ => (anchor.min(self.cursor), anchor.max(self.cursor)),
+        };
+        Some((start, end, kind))
+    }
+
+    /// A short description of the state to show to the user.
+    pub fn status(&self) -> String {
+        match (&self.query, self.anchor) {
+            (Some((query, true)), _) => format!("/{query}"),
+            (Some((query, false)), _) => format!("?{query}"),
+            (None, None) => "COPY".to_string(),
+            (None, Some((_, SelectionKind::Character))) => "VISUAL".to_string(),
+            (None, Some((_, SelectionKind::Line))) => "VISUAL LINE".to_string(),
+            (None, Some((_, SelectionKind::Block))) => "VISUAL BLOCK".to_string(),
+        }
+    }
+
+    pub fn handle(&mut self, input: Input, buffer: &impl Buffer) -> Outcome {
+        if self.query.is_some() {
+            self.edit_query(input, buffer);
+            return Outcome::Continue;
+        }
+
+        let (first, last) = buffer.rows();
+        let (top, height) = buffer.screen();
+        let cursor = self.cursor;
+        let pending_g = std::mem::take(&mut self.pending_g);
+        match input {
+            Input::Char('h') | Input::Left => self.cursor.column -= 1,
+            Input::Char('l') | Input::Right => self.cursor.column += 1,
+            Input::Char('k') | Input::Up => self.cursor.row -= 1,
+            Input::Char('j') | Input::Down => self.cursor.row += 1,
+            Input::Char('0') => self.cursor.column = 0,
+            Input::Char('^') => {
+                let line = buffer.line(cursor.row);
+                self.cursor.column = line.chars().take_while(|c| c.is_whitespace()).count() as i64;
+            }
+            Input::Char('$') => {
+                let length = buffer.line(cursor.row).chars().count() as i64;
+                self.cursor.column = (length - 1).max(0);
+            }
+            Input::Char('w') => self.cursor = next_word(buffer, cursor, last),
+            Input::Char('b') => self.cursor = previous_word(buffer, cursor, first),
+            Input::Char('e') => self.cursor = word_end(buffer, cursor, last),
+            Input::Char('g') if pending_g => {
+                self.cursor = Position {
+                    row: first,
+                    column: 0,
+                }
+            }
+            Input::Char('g') => self.pending_g = true,
+            Input::Char('G') => {
+                self.cursor = Position {
+                    row: last,
+                    column: 0,
+                }
+            }
+            Input::Char('H') => self.cursor.row = top,
+            Input::Char('M') => self.cursor.row = top + height / 2,
+            Input::Char('L') => self.cursor.row = top + height - 1,
+            Input::Ctrl('u') => self.cursor.row -= height / 2,
+            Input::Ctrl('d') => self.cursor.row += height / 2,
+            Input::Ctrl('b') => self.cursor.row -= height,
+            Input::Ctrl('f') => self.cursor.row += height,
+            Input::Char('v') => self.toggle_selection(SelectionKind::Character),
+            Input::Char('V') => self.toggle_selection(SelectionKind::Line),
+            Input::Ctrl('v') => self.toggle_selection(SelectionKind::Block),
+            Input::Char('/') => self.query = Some((String::new(), true)),
+            Input::Char('?') => self.query = Some((String::new(), false)),
+            Input::Char('n') => self.repeat_search(buffer, false),
+            Input::Char('N') => self.repeat_search(buffer, true),
+            Input::Char('y') | Input::Enter => {
+                return match self.selected_text(buffer) {
+                    Some(text) => Outcome::Yank(text),
+                    None => Outcome::Continue,
+                }
+            }
+            Input::Escape if self.anchor.is_some() => self.anchor = None,
+            Input::Char('q') | Input::Escape | Input::Ctrl('c') => return Outcome::Exit,
+            _ => {}
+        }
+
+        self.cursor.row = self.cursor.row.clamp(first, last);
+        self.cursor.column = self.cursor.column.max(0);
+        Outcome::Continue
+    }
+
+    fn toggle_selection(&mut self, kind: SelectionKind) {
+        self.anchor = match self.anchor {
+            Some((_, current)) if current == kind => None,
+            Some((anchor, _)) => Some((anchor, kind)),
+            None => Some((self.cursor, kind)),
+        };
+    }
+
+    fn edit_query(&mut self, input: Input, buffer: &impl Buffer) {
+        let Some((query, forward)) = self.query.as_mut() else {
+            return;
+        };
+        match input {
+            Input::Char(c) => query.push(c),
+            Input::Backspace => {
+                if query.pop().is_none() {
+                    self.query = None;
+                }
+            }
+            Input::Enter => {
+                let search = (query.clone(), *forward);
+                self.query = None;
+                if !search.0.is_empty() {
+                    self.last_search = Some(search);
+                }
+                self.repeat_search(buffer, false);
+            }
+            Input::Escape | Input::Ctrl('c') => self.query = None,
+            _ => {}
+        }
+    }
+
+    /// Move the cursor to the next match of the last search, in the opposite
+    /// direction if `reverse` is set.
+    fn repeat_search(&mut self, buffer: &impl Buffer, reverse: bool) {
+        let Some((query, forward)) = self.last_search.clone() else {
+            return;
+        };
+        let found = if forward != reverse {
+            search_forward(buffer, self.cursor, &query)
+        } else {
+            search_backward(buffer, self.cursor, &query)
+        };
+        if let Some(position) = found {
+            self.cursor = position;
+        }
+    }
+
+    fn selected_text(&self, buffer: &impl Buffer) -> Option<String> {
+        let (start, end, kind) = self.selection()?;
+        let lines = (start.row..=end.row).map(|row| {
+            let line: Vec<char> = buffer.line(row).chars().collect();
+            let (from, to) = match kind {
+                SelectionKind::Line => (0, line.len()),
+                SelectionKind::Block => (start.column as usize, end.column as usize + 1),
+                SelectionKind::Character => (
+                    if row == start.row {
+                        start.column as usize
+                    } else {
+                        0
+                    },
+                    if row == end.row {
+                        end.column as usize + 1
+                    } else {
+                        line.len()
+                    },
+                ),
+            };
+            let to = to.min(line.len());
+            let from = from.min(to);
+            line[from..to].iter().collect::<String>()
+        });
+        Some(lines.collect::<Vec<_>>().join("\n"))
+    }
+}
+
+/// The first cell of every character of `line`, followed by the cell after
+/// the last character. `width` gives the cells taken by a character, wide
+/// characters take two and combining characters none. Tabs reach to the
+/// next tab stop.
+pub fn cells(line: &str, width: impl Fn(char) -> i64) -> Vec<i64> {
+    let mut cells = vec![0];
+    let mut cell = 0;
+    for c in line.chars() {
+        cell += if c == '\t' {
+            TAB_WIDTH - cell % TAB_WIDTH
+        } else {
+            width(c)
+        };
+        cells.push(cell);
+    }
+    cells
+}
+
+/// The first and the last cell of the character at `column`. Past the end of
+/// the text every character takes one cell.
+pub fn cell_span(cells: &[i64], column: i64) -> (i64, i64) {
+    let column = column.max(0) as usize;
+    match (cells.get(column), cells.get(column + 1)) {
+        (Some(first), Some(next)) => (*first, (*next - 1).max(*first)),
+        _ => {
+            let cell = cells[cells.len() - 1] + (column - (cells.len() - 1)) as i64;
+            (cell, cell)
+        }
+    }
+}
+
+/// The column in characters of the character covering `cell`.
+pub fn char_column(cells: &[i64], cell: i64) -> i64 {
+    let characters = cells.len() as i64 - 1;
+    let end = cells[cells.len() - 1];
+    if cell >= end {
+        characters + cell - end
+    } else {
+        cells[..cells.len() - 1]
+            .iter()
+            .rposition(|first| *first <= cell)
+            .unwrap_or_default() as i64
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The class of a character for word motions: whitespace, word characters
+/// and punctuation.
+fn class(c: Option<char>) -> u8 {
+    match c {
+        None => 0,
+        Some(c) if c.is_whitespace() => 0,
+        Some(c) if is_word(c) => 1,
+        Some(_) => 2,
+    }
+}
+
+fn char_at(buffer: &impl Buffer, position: Position) -> Option<char> {
+    buffer
+        .line(position.row)
+        .chars()
+        .nth(position.column as usize)
+}
+
+/// The position after `position`, continuing on the next row at the end of
+/// the line.
+fn step_forward(buffer: &impl Buffer, position: Position, last: i64) -> Option<Position> {
+    let length = buffer.line(position.row).chars().count() as i64;
+    if position.column + 1 < length {
+        Some(Position {
+            column: position.column + 1,
+            ..position
+        })
+    } else if position.row < last {
+        Some(Position {
+            row: position.row + 1,
+            column: 0,
+        })
+    } else {
+        None
+    }
+}
+
+fn step_backward(buffer: &impl Buffer, position: Position, first: i64) -> Option<Position> {
+    if position.column > 0 {
+        Some(Position {
+            column: position.column - 1,
+            ..position
+        })
+    } else if position.row > first {
+        let length = buffer.line(position.row - 1).chars().count() as i64;
+        Some(Position {
+            row: position.row - 1,
+            column: (length - 1).max(0),
+        })
+    } else {
+        None
+    }
+}
+
+fn next_word(buffer: &impl Buffer, mut position: Position, last: i64) -> Position {
+    let start = class(char_at(buffer, position));
+    // Leave the current word, then skip the whitespace after it.
+    while let Some(next) = step_forward(buffer, position, last) {
+        let row_changed = next.row != position.row;
+        position = next;
+        let current = class(char_at(buffer, position));
+        if row_changed || current != start {
+            break;
+        }
+    }
+    while class(char_at(buffer, position)) == 0 {
+        match step_forward(buffer, position, last) {
+            Some(next) => position = next,
+            None => break,
+        }
+    }
+    position
+}
+
+fn previous_word(buffer: &impl Buffer, mut position: Position, first: i64) -> Position {
+    // Skip the whitespace before the cursor, then move to the start of the
+    // word in front of it.
+    while let Some(previous) = step_backward(buffer, position, first) {
+        position = previous;
+        if class(char_at(buffer, position)) != 0 {
+            break;
+        }
+    }
+    let current = class(char_at(buffer, position));
+    while let Some(previous) = step_backward(buffer, position, first) {
+        if previous.row != position.row || class(char_at(buffer, previous)) != current {
+            break;
+        }
+        position = previous;
+    }
+    position
+}
+
+fn word_end(buffer: &impl Buffer, mut position: Position, last: i64) -> Position {
+    while let Some(next) = step_forward(buffer, position, last) {
+        position = next;
+        if class(char_at(buffer, position)) != 0 {
+            break;
+        }
+    }
+    let current = class(char_at(buffer, position));
+    while let Some(next) = step_forward(buffer, position, last) {
+        if next.row != position.row || class(char_at(buffer, next)) != current {
+            break;
+        }
+        position = next;
+    }
+    position
+}
+
+fn search_forward(buffer: &impl Buffer, from: Position, query: &str) -> Option<Position> {
+    let (_, last) = buffer.rows();
+    (from.row..=last).find_map(|row| {
+        let line = buffer.line(row);
+        let skip = if row == from.row { from.column + 1 } else { 0 } as usize;
+        let offset = line.char_indices().nth(skip).map(|(index, _)| index)?;
+        let index = line[offset..].find(query)? + offset;
+        Some(Position {
+            row,
+            column: line[..index].chars().count() as i64,
+        })
+    })
+}
+
+fn search_backward(buffer: &impl Buffer, from: Position, query: &str) -> Option<Position> {
+    let (first, _) = buffer.rows();
+    (first..=from.row).rev().find_map(|row| {
+        let line = buffer.line(row);
+        let end = if row == from.row {
+            line.char_indices()
+                .nth(from.column as usize)
+                .map_or(line.len(), |(index, _)| index)
+        } else {
+            line.len()
+        };
+        let index = line[..end].rfind(query)?;
+        Some(Position {
+            row,
+            column: line[..index].chars().count() as i64,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Lines(Vec<&'static str>);
+
+    impl Buffer for Lines {
+        fn line(&self, row: i64) -> String {
+            self.0.get(row as usize).unwrap_or(&"").to_string()
+        }
+
+        fn rows(&self) -> (i64, i64) {
+            (0, self.0.len() as i64 - 1)
+        }
+
+        fn screen(&self) -> (i64, i64) {
+            (1, 3)
+        }
+    }
+
+    fn position(row: i64, column: i64) -> Position {
+        Position { row, column }
+    }
+
+    /// Feed `keys` to a copy mode starting at `start` and return the cursor.
+    fn cursor(lines: &Lines, start: Position, keys: &str) -> Position {
+        let mut copy_mode = CopyMode::new(start);
+        for key in keys.chars() {
+            copy_mode.handle(Input::Char(key), lines);
+        }
+        copy_mode.cursor
+    }
+
+    /// Feed `keys` and return what is yanked at the end.
+    fn yank(lines: &Lines, start: Position, keys: &str) -> Outcome {
+        let mut copy_mode = CopyMode::new(start);
+        for key in keys.chars() {
+            copy_mode.handle(Input::Char(key), lines);
+        }
+        copy_mode.handle(Input::Char('y'), lines)
+    }
+
+    #[test]
+    fn character_motions() {
+        let lines = Lines(vec!["abc", "  def", ""]);
+        assert_eq!(cursor(&lines, position(0, 0), "hk"), position(0, 0));
+        assert_eq!(cursor(&lines, position(0, 0), "llj"), position(1, 2));
+        assert_eq!(cursor(&lines, position(0, 0), "jjj"), position(2, 0));
+        assert_eq!(cursor(&lines, position(1, 4), "0"), position(1, 0));
+        assert_eq!(cursor(&lines, position(1, 0), "^"), position(1, 2));
+        assert_eq!(cursor(&lines, position(1, 0), "$"), position(1, 4));
+        assert_eq!(cursor(&lines, position(2, 3), "$"), position(2, 0));
+    }
+
+    #[test]
+    fn word_motions() {
+        let lines = Lines(vec!["foo.bar  baz", "qux"]);
+        assert_eq!(cursor(&lines, position(0, 0), "w"), position(0, 3));
+        assert_eq!(cursor(&lines, position(0, 0), "ww"), position(0, 4));
+        assert_eq!(cursor(&lines, position(0, 0), "www"), position(0, 9));
+        assert_eq!(cursor(&lines, position(0, 9), "w"), position(1, 0));
+        assert_eq!(cursor(&lines, position(0, 0), "e"), position(0, 2));
+        assert_eq!(cursor(&lines, position(0, 4), "e"), position(0, 6));
+        assert_eq!(cursor(&lines, position(0, 6), "e"), position(0, 11));
+        assert_eq!(cursor(&lines, position(1, 0), "b"), position(0, 9));
+        assert_eq!(cursor(&lines, position(0, 9), "b"), position(0, 4));
+        assert_eq!(cursor(&lines, position(0, 4), "b"), position(0, 3));
+        assert_eq!(cursor(&lines, position(0, 0), "b"), position(0, 0));
+    }
+
+    #[test]
+    fn line_motions() {
+        let lines = Lines(vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(cursor(&lines, position(3, 0), "gg"), position(0, 0));
+        assert_eq!(cursor(&lines, position(3, 0), "g"), position(3, 0));
+        assert_eq!(cursor(&lines, position(3, 0), "gjg"), position(4, 0));
+        assert_eq!(cursor(&lines, position(0, 0), "G"), position(4, 0));
+        assert_eq!(cursor(&lines, position(0, 0), "H"), position(1, 0));
+        assert_eq!(cursor(&lines, position(0, 0), "M"), position(2, 0));
+        assert_eq!(cursor(&lines, position(0, 0), "L"), position(3, 0));
+
+        let mut copy_mode = CopyMode::new(position(0, 0));
+        copy_mode.handle(Input::Ctrl('f'), &lines);
+        assert_eq!(copy_mode.cursor, position(3, 0));
+        copy_mode.handle(Input::Ctrl('f'), &lines);
+        assert_eq!(copy_mode.cursor, position(4, 0));
+        copy_mode.handle(Input::Ctrl('u'), &lines);
+        assert_eq!(copy_mode.cursor, position(3, 0));
+    }
+
+    #[test]
+    fn selections() {
+        let lines = Lines(vec!["abcd", "efgh", "ij"]);
+        assert_eq!(yank(&lines, position(0, 1), ""), Outcome::Continue);
+        assert_eq!(
+            yank(&lines, position(0, 1), "vjl"),
+            Outcome::Yank("bcd\nefg".to_string())
+        );
+        assert_eq!(
+            yank(&lines, position(1, 2), "vkh"),
+            Outcome::Yank("bcd\nefg".to_string())
+        );
+        assert_eq!(
+            yank(&lines, position(0, 1), "Vj"),
+            Outcome::Yank("abcd\nefgh".to_string())
+        );
+        assert_eq!(
+            yank(&lines, position(0, 2), "vlllj"),
+            Outcome::Yank("cd\nefgh".to_string())
+        );
+
+        let mut copy_mode = CopyMode::new(position(0, 1));
+        copy_mode.handle(Input::Ctrl('v'), &lines);
+        copy_mode.handle(Input::Char('j'), &lines);
+        copy_mode.handle(Input::Char('j'), &lines);
+        copy_mode.handle(Input::Char('l'), &lines);
+        assert_eq!(copy_mode.status(), "VISUAL BLOCK");
+        assert_eq!(
+            copy_mode.handle(Input::Enter, &lines),
+            Outcome::Yank("bc\nfg\nj".to_string())
+        );
+
+        // Escape drops the selection first and leaves copy mode after.
+        assert_eq!(copy_mode.handle(Input::Escape, &lines), Outcome::Continue);
+        assert_eq!(copy_mode.status(), "COPY");
+        assert_eq!(copy_mode.handle(Input::Escape, &lines), Outcome::Exit);
+    }
+
+    #[test]
+    fn search() {
+        let lines = Lines(vec!["one two", "two one", "three"]);
+        let mut copy_mode = CopyMode::new(position(0, 0));
+        for key in "/twox".chars() {
+            copy_mode.handle(Input::Char(key), &lines);
+        }
+        assert_eq!(copy_mode.status(), "/twox");
+        copy_mode.handle(Input::Backspace, &lines);
+        copy_mode.handle(Input::Enter, &lines);
+        assert_eq!(copy_mode.cursor, position(0, 4));
+        copy_mode.handle(Input::Char('n'), &lines);
+        assert_eq!(copy_mode.cursor, position(1, 0));
+        copy_mode.handle(Input::Char('n'), &lines);
+        assert_eq!(copy_mode.cursor, position(1, 0));
+        copy_mode.handle(Input::Char('N'), &lines);
+        assert_eq!(copy_mode.cursor, position(0, 4));
+
+        for key in "?one".chars() {
+            copy_mode.handle(Input::Char(key), &lines);
+        }
+        copy_mode.handle(Input::Enter, &lines);
+        assert_eq!(copy_mode.cursor, position(0, 0));
+        copy_mode.handle(Input::Char('N'), &lines);
+        assert_eq!(copy_mode.cursor, position(1, 4));
+    }
+
+    /// Characters from U+1100 take two cells and U+0300 to U+036F none, like
+    /// wide and combining characters do.
+    fn width(c: char) -> i64 {
+        match c {
+            '\u{300}'..='\u{36f}' => 0,
+            '\u{1100}'.. => 2,
+            _ => 1,
+        }
+    }
+
+    #[test]
+    fn cells_of_characters() {
+        assert_eq!(cells("", width), [0]);
+        assert_eq!(cells("ab", width), [0, 1, 2]);
+        assert_eq!(cells("a日b", width), [0, 1, 3, 4]);
+        assert_eq!(cells("e\u{301}x", width), [0, 1, 1, 2]);
+        assert_eq!(cells("a\tb\t\tc", width), [0, 1, 8, 9, 16, 24, 25]);
+    }
+
+    #[test]
+    fn cell_spans() {
+        let cells = cells("a日\u{301}b", width);
+        assert_eq!(cell_span(&cells, 0), (0, 0));
+        assert_eq!(cell_span(&cells, 1), (1, 2));
+        assert_eq!(cell_span(&cells, 2), (3, 3));
+        assert_eq!(cell_span(&cells, 3), (3, 3));
+        assert_eq!(cell_span(&cells, 4), (4, 4));
+        assert_eq!(cell_span(&cells, 6), (6, 6));
+        assert_eq!(cell_span(&cells, -1), (0, 0));
+    }
+
+    #[test]
+    fn char_columns() {
+        let cells = cells("a日\u{301}b", width);
+        assert_eq!(char_column(&cells, 0), 0);
+        assert_eq!(char_column(&cells, 1), 1);
+        assert_eq!(char_column(&cells, 2), 1);
+        assert_eq!(char_column(&cells, 3), 3);
+        assert_eq!(char_column(&cells, 4), 4);
+        assert_eq!(char_column(&cells, 6), 6);
+        assert_eq!(char_column(&[0], 2), 2);
+    }
+}
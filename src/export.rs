@@ -0,0 +1,297 @@
+//! Conversion of the terminal contents into the formats they can be exported
+//! to. VTE only renders its contents as plain text or HTML, so text with ANSI
+//! escape sequences is derived from the HTML.
+
+use crate::theme::Theme;
+use std::path::Path;
+
+const MAX_ENTITY_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Ansi,
+    Html,
+}
+
+impl Format {
+    pub const ALL: [Self; 3] = [Self::Text, Self::Ansi, Self::Html];
+
+    /// Pick the format from the file extension, if it names one.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension().and_then(|extension| extension.to_str())?;
+        match extension.to_ascii_lowercase().as_str() {
+            "txt" => Some(Self::Text),
+            "ansi" => Some(Self::Ansi),
+            "html" | "htm" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Text => "Plain Text",
+            Self::Ansi => "Text with Colors",
+            Self::Html => "HTML",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Ansi => "ansi",
+            Self::Html => "html",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Attribute {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Blink,
+    Foreground(String),
+    Background(String),
+    Other,
+}
+
+impl Attribute {
+    fn parse(tag: &str) -> Self {
+        match tag_name(tag).as_str() {
+            "b" => Self::Bold,
+            "i" => Self::Italic,
+            "u" => Self::Underline,
+            "strike" | "s" => Self::Strikethrough,
+            "blink" => Self::Blink,
+            "font" => color_of(tag, "color=\"").map_or(Self::Other, Self::Foreground),
+            "span" => color_of(tag, "background-color:").map_or(Self::Other, Self::Background),
+            _ => Self::Other,
+        }
+    }
+
+    fn sgr(&self) -> Option<String> {
+        match self {
+            Self::Bold => Some("1".to_string()),
+            Self::Italic => Some("3".to_string()),
+            Self::Underline => Some("4".to_string()),
+            Self::Blink => Some("5".to_string()),
+            Self::Strikethrough => Some("9".to_string()),
+            Self::Foreground(color) => rgb(color).map(|(r, g, b)| format!("38;2;{r};{g};{b}")),
+            Self::Background(color) => rgb(color).map(|(r, g, b)| format!("48;2;{r};{g};{b}")),
+            Self::Other => None,
+        }
+    }
+}
+
+/// Convert the HTML rendered by VTE into text with SGR escape sequences.
+pub fn ansi(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut attributes: Vec<(String, Attribute)> = Vec::new();
+    let mut current = sgr(&[]);
+    let mut rest = html;
+
+    loop {
+        let start = rest.find(['<', '&']).unwrap_or(rest.len());
+        if start > 0 {
+            push_styled(&mut text, &mut current, &attributes, &rest[..start]);
+        }
+        rest = &rest[start..];
+
+        if rest.is_empty() {
+            break;
+        } else if rest.starts_with('&') {
+            match rest.find(';').filter(|end| *end <= MAX_ENTITY_LENGTH) {
+                Some(end) => {
+                    let entity = unescape(&rest[1..end]);
+                    push_styled(&mut text, &mut current, &attributes, &entity);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    push_styled(&mut text, &mut current, &attributes, "&");
+                    rest = &rest[1..];
+                }
+            }
+            continue;
+        }
+
+        let Some(end) = rest.find('>') else {
+            text.push_str(rest);
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let name = tag_name(tag);
+        match name.as_str() {
+            "br" => text.push('\n'),
+            "pre" | "/pre" => {}
+            _ => {
+                if let Some(name) = name.strip_prefix('/') {
+                    // Close the innermost attribute opened by the same tag.
+                    let position = attributes.iter().rposition(|(tag, _)| tag == name);
+                    if let Some(position) = position {
+                        attributes.remove(position);
+                    }
+                } else {
+                    attributes.push((name, Attribute::parse(tag)));
+                }
+            }
+        }
+    }
+
+    if current != sgr(&[]) {
+        text.push_str(&sgr(&[]));
+    }
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// Wrap the HTML rendered by VTE into a standalone document styled with the
+/// colors of the theme.
+pub fn html(html: &str, theme: &Theme) -> String {
+    format!(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>Galactic</title>\n\
+         <style>\n\
+         body {{ margin: 0; padding: 1em; color: {}; background-color: {}; }}\n\
+         pre {{ margin: 0; font-family: monospace; white-space: pre-wrap; }}\n\
+         </style>\n\
+         </head>\n\
+         <body>\n\
+         {html}\n\
+         </body>\n\
+         </html>\n",
+        theme.foreground, theme.background
+    )
+}
+
+/// Append `chunk` to `text`, preceded by an SGR sequence if the attributes
+/// differ from the `current` ones.
+fn push_styled(
+    text: &mut String,
+    current: &mut String,
+    attributes: &[(String, Attribute)],
+    chunk: &str,
+) {
+    let next = sgr(attributes);
+    if next != *current {
+        text.push_str(&next);
+        *current = next;
+    }
+    text.push_str(chunk);
+}
+
+/// The SGR sequence that resets the text attributes and applies `attributes`.
+fn sgr(attributes: &[(String, Attribute)]) -> String {
+    let parameters: Vec<String> = attributes
+        .iter()
+        .filter_map(|(_, attribute)| attribute.sgr())
+        .collect();
+    if parameters.is_empty() {
+        "\x1b[0m".to_string()
+    } else {
+        format!("\x1b[0;{}m", parameters.join(";"))
+    }
+}
+
+fn tag_name(tag: &str) -> String {
+    let name = tag.split_whitespace().next().unwrap_or_default();
+    name.trim_end_matches('/').to_ascii_lowercase()
+}
+
+fn color_of(tag: &str, prefix: &str) -> Option<String> {
+    let start = tag.find(prefix)? + prefix.len();
+    let color = tag[start..].trim_start();
+    Some(color.get(..7)?.to_string())
+}
+
+fn rgb(color: &str) -> Option<(u8, u8, u8)> {
+    let color = color.strip_prefix('#')?;
+    let r = u8::from_str_radix(color.get(0..2)?, 16).ok()?;
+    let g = u8::from_str_radix(color.get(2..4)?, 16).ok()?;
+    let b = u8::from_str_radix(color.get(4..6)?, 16).ok()?;
+    Some((r, g, b))
+}
+
+fn unescape(entity: &str) -> String {
+    match entity {
+        "lt" => "<".to_string(),
+        "gt" => ">".to_string(),
+        "amp" => "&".to_string(),
+        "quot" => "\"".to_string(),
+        "apos" => "'".to_string(),
+        "nbsp" => " ".to_string(),
+        _ => entity
+            .strip_prefix("#x")
+            .and_then(|code| u32::from_str_radix(code, 16).ok())
+            .or_else(|| entity.strip_prefix('#').and_then(|code| code.parse().ok()))
+            .and_then(char::from_u32)
+            .map_or_else(|| format!("&{entity};"), String::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(Format::from_path(Path::new("a.txt")), Some(Format::Text));
+        assert_eq!(Format::from_path(Path::new("a.ANSI")), Some(Format::Ansi));
+        assert_eq!(Format::from_path(Path::new("a.htm")), Some(Format::Html));
+        assert_eq!(Format::from_path(Path::new("a.log")), None);
+        assert_eq!(Format::from_path(Path::new("scrollback")), None);
+    }
+
+    #[test]
+    fn ansi_plain() {
+        assert_eq!(ansi("<pre>a<br>b</pre>"), "a\nb\n");
+    }
+
+    #[test]
+    fn ansi_entities() {
+        assert_eq!(
+            ansi("<pre>&lt;a&gt; &amp; &quot;b&quot; &#65;&#x42;</pre>"),
+            "<a> & \"b\" AB\n"
+        );
+        assert_eq!(ansi("<pre>a & b</pre>"), "a & b\n");
+        assert_eq!(ansi("<pre>&unknown;</pre>"), "&unknown;\n");
+    }
+
+    #[test]
+    fn ansi_colors() {
+        assert_eq!(
+            ansi("<pre><font color=\"#ff0080\">a</font>b</pre>"),
+            "\x1b[0;38;2;255;0;128ma\x1b[0mb\n"
+        );
+        assert_eq!(
+            ansi("<pre><span style=\"background-color:#000010\">a</span></pre>"),
+            "\x1b[0;48;2;0;0;16ma\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn ansi_nested() {
+        assert_eq!(
+            ansi(
+                "<pre><font color=\"#ff0000\"><span style=\"background-color:#00ff00\">\
+                 <b>a</b>b</span>c</font>d</pre>"
+            ),
+            "\x1b[0;38;2;255;0;0;48;2;0;255;0;1ma\
+             \x1b[0;38;2;255;0;0;48;2;0;255;0mb\
+             \x1b[0;38;2;255;0;0mc\
+             \x1b[0md\n"
+        );
+        assert_eq!(
+            ansi("<pre><span>a<span style=\"background-color:#0000ff\">b</span>c</span></pre>"),
+            "a\x1b[0;48;2;0;0;255mb\x1b[0mc\n"
+        );
+    }
+}
@@ -0,0 +1,33 @@
+//! Fuzzy matching of a query against the entries of the command palette.
+
+/// Score how well `query` matches `text`, or `None` if the characters of the
+/// query do not appear in `text` in order. Matching ignores case. Runs of
+/// consecutive characters and matches at the start of words score higher,
+/// and shorter texts win ties. An empty query matches all texts equally.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    if query.trim().is_empty() {
+        return Some(0);
+    }
+
+    let text: Vec<char> = text.chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for c in query.chars().filter(|c| !c.is_whitespace()) {
+        let index = (position..text.len()).find(|&index| chars_match(c, text[index]))?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == index) {
+            score += 4;
+        }
+        if index == 0 || !text[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(index);
+        position = index + 1;
+    }
+    Some(score * 100 - text.len() as i64)
+}
+
+fn chars_match(query: char, text: char) -> bool {
+    query.to_lowercase().eq(text.to_lowercase())
+}
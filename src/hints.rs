@@ -0,0 +1,180 @@
+//! Detection of text on the screen that can be picked with the keyboard in
+//! hint mode, like URLs, paths, hashes and IP addresses. The patterns are
+//! matched by VTE, so they match the same text VTE highlights under the
+//! pointer. Positions are absolute rows and columns in the VTE buffer,
+//! columns are counted in characters.
+
+use crate::{blocks::Position, config::hints::HintAction};
+use gtk::glib;
+
+const URL: &str = r#"(?:https?|ftp|file)://[^\s<>"'`]*[^\s<>"'`.,;:!?)\]}]"#;
+const PATH: &str = r#"(?<=^|[\s'"(=:])(?:~|\.{1,2}|[\w.-]+)?/[\w.@%+~-]+(?:/[\w.@%+~-]+)*/?"#;
+const IP: &str = concat!(
+    r"\b(?:\d{1,3}\.){3}\d{1,3}\b",
+    // IPv6 addresses have eight groups or skip some with `::`, so times like
+    // 12:34:56 do not match.
+    r"|(?<![\w:])(?:(?:[0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}",
+    r"|(?:(?:[0-9a-fA-F]{1,4}:){1,6}|:):(?:[0-9a-fA-F]{1,4}:){0,5}[0-9a-fA-F]{1,4}",
+    r"|(?:[0-9a-fA-F]{1,4}:){1,7}:)(?![\w:])",
+);
+// Hashes need a letter, so plain numbers are not taken for hashes.
+const HASH: &str = r"\b(?=[0-9]*[a-f])[0-9a-f]{7,64}\b";
+
+/// Lets `^` and `$` match at line breaks, which VTE requires of the patterns
+/// it highlights.
+const PCRE2_MULTILINE: u32 = 0x0000_0400;
+
+/// Replaces all matches instead of the first one.
+const PCRE2_SUBSTITUTE_GLOBAL: u32 = 0x0000_0100;
+
+/// Surround the matches in substituted text. Control characters are never
+/// part of the text on the screen.
+const MATCH_START: char = '\u{1}';
+const MATCH_END: char = '\u{2}';
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: vte::Regex,
+    source: String,
+    action: Option<HintAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    pub start: Position,
+    pub text: String,
+
+    /// The action of the pattern, if it overrides the default action.
+    pub action: Option<HintAction>,
+}
+
+impl Pattern {
+    pub fn new(regex: &str, action: Option<HintAction>) -> Result<Self, glib::Error> {
+        Ok(Self {
+            regex: vte::Regex::for_match(regex, PCRE2_MULTILINE)?,
+            source: regex.to_string(),
+            action,
+        })
+    }
+
+    /// The built-in patterns for URLs, paths, IP addresses and hashes.
+    pub fn defaults() -> Vec<Self> {
+        // Unwrapping is safe because the built-in patterns are valid.
+        [URL, PATH, IP, HASH]
+            .iter()
+            .map(|regex| Self::new(regex, None).unwrap())
+            .collect()
+    }
+
+    pub fn regex(&self) -> &vte::Regex {
+        &self.regex
+    }
+
+    /// The start and the end of every match in `text`, counted in
+    /// characters. VTE has no function to list matches, so they are marked
+    /// by substitution.
+    fn matches(&self, text: &str) -> Vec<(usize, usize)> {
+        let replacement = format!("{MATCH_START}$0{MATCH_END}");
+        let marked = match self
+            .regex
+            .substitute(text, &replacement, PCRE2_SUBSTITUTE_GLOBAL)
+        {
+            Ok(marked) => marked,
+            Err(error) => {
+                tracing::warn!("Failed to match hint pattern `{}`: {error}.", self.source);
+                return Vec::new();
+            }
+        };
+
+        let mut matches = Vec::new();
+        let mut start = 0;
+        let mut offset = 0;
+        for c in marked.chars() {
+            match c {
+                MATCH_START => start = offset,
+                MATCH_END => matches.push((start, offset)),
+                _ => offset += 1,
+            }
+        }
+        matches
+    }
+}
+
+/// Find the hints in `lines`, each given with its row. Patterns that come
+/// first win over later patterns matching overlapping text. Matches spanning
+/// several lines are left out.
+pub fn find(lines: &[(i64, String)], patterns: &[Pattern]) -> Vec<Hint> {
+    // The lines are matched at once, the markers are replaced so they can
+    // only come from matches.
+    let text = lines
+        .iter()
+        .map(|(_, line)| line.replace([MATCH_START, MATCH_END], " "))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let characters: Vec<char> = text.chars().collect();
+
+    let mut found: Vec<(usize, usize, Option<HintAction>)> = Vec::new();
+    for pattern in patterns {
+        for (start, end) in pattern.matches(&text) {
+            if start == end || characters[start..end].contains(&'\n') {
+                continue;
+            }
+            let overlaps = found
+                .iter()
+                .any(|(other_start, other_end, _)| start < *other_end && *other_start < end);
+            if !overlaps {
+                found.push((start, end, pattern.action));
+            }
+        }
+    }
+    found.sort_by_key(|(start, _, _)| *start);
+
+    let mut line_starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for (row, line) in lines {
+        line_starts.push((offset, *row));
+        offset += line.chars().count() + 1;
+    }
+    found
+        .into_iter()
+        .map(|(start, end, action)| {
+            let index = line_starts.partition_point(|(line_start, _)| *line_start <= start) - 1;
+            let (line_start, row) = line_starts[index];
+            Hint {
+                start: Position {
+                    row,
+                    column: (start - line_start) as i64,
+                },
+                text: characters[start..end].iter().collect(),
+                action,
+            }
+        })
+        .collect()
+}
+
+/// Labels of equal length for `count` hints made of the characters of
+/// `alphabet`. The first labels go to the last hints, which are closest to
+/// the prompt.
+pub fn labels(count: usize, alphabet: &str) -> Vec<String> {
+    let alphabet: Vec<char> = alphabet.chars().collect();
+    if alphabet.len() < 2 {
+        return Vec::new();
+    }
+
+    let mut length = 1;
+    while alphabet.len().pow(length) < count {
+        length += 1;
+    }
+    let mut labels: Vec<String> = (0..count)
+        .map(|mut index| {
+            let mut label = String::new();
+            for _ in 0..length {
+                label.insert(0, alphabet[index % alphabet.len()]);
+                index /= alphabet.len();
+            }
+            label
+        })
+        .collect();
+    labels.reverse();
+    labels
+}
@@ -1,14 +1,30 @@
 use adw::prelude::*;
+use control::{Request, Response};
 use gtk::{
     gio::{self},
     glib,
 };
+use options::{Command, Options};
+use std::path::Path;
 use tracing_subscriber::EnvFilter;
 use ui::application::Application;
 
+mod blocks;
 mod config;
 mod constants;
+mod control;
+mod copy_mode;
+mod export;
+mod fuzzy;
+mod hints;
 mod options;
+mod osc;
+mod paste;
+mod process;
+mod quote;
+mod recording;
+mod shell_integration;
+mod state;
 mod theme;
 mod ui;
 
@@ -17,8 +33,70 @@ fn main() -> glib::ExitCode {
         .with_env_filter(EnvFilter::from_env("GALACTIC_LOG"))
         .init();
 
-    let application = Application::new(&gio::ApplicationFlags::NON_UNIQUE);
+    // Handle subcommands that do not need a window.
+    let options = Options::parse();
+    match options.command {
+        Some(Command::ShellIntegration { shell }) => {
+            print!("{}", shell.script());
+            return glib::ExitCode::SUCCESS;
+        }
+        Some(Command::Msg { socket, request }) => return message(socket.as_deref(), request),
+        _ => {}
+    }
 
-    static EMPTY_ARGS: Vec<String> = vec![];
-    application.run_with_args(&EMPTY_ARGS)
+    // The arguments are handled by the application, which forwards them to
+    // the running instance in single-instance mode. The drop-down window
+    // always belongs to a single instance, so it can be toggled.
+    let mut flags = gio::ApplicationFlags::HANDLES_COMMAND_LINE;
+    if !options.single_instance && !options.dropdown {
+        flags |= gio::ApplicationFlags::NON_UNIQUE;
+    }
+    let application = Application::new(&flags);
+    application.run()
+}
+
+/// Send a request to a running instance and print its response.
+fn message(socket: Option<&Path>, mut request: Request) -> glib::ExitCode {
+    // Target the terminal and directory the command is run in by default.
+    let current_terminal = std::env::var(control::TERMINAL_VARIABLE)
+        .ok()
+        .and_then(|id| id.parse().ok());
+    match &mut request {
+        Request::NewWindow { working_directory } | Request::NewTab { working_directory } => {
+            if working_directory.is_none() {
+                *working_directory = std::env::current_dir().ok();
+            }
+        }
+        Request::SendText { terminal, .. } | Request::SetTitle { terminal, .. } => {
+            *terminal = terminal.or(current_terminal);
+        }
+        Request::List | Request::Focus { .. } => {}
+    }
+
+    match control::send(socket, &request) {
+        Ok(Response::Done) => glib::ExitCode::SUCCESS,
+        Ok(Response::Created { terminal }) => {
+            println!("{terminal}");
+            glib::ExitCode::SUCCESS
+        }
+        Ok(Response::Terminals { terminals }) => {
+            for terminal in terminals {
+                let directory = terminal
+                    .directory
+                    .map(|directory| directory.display().to_string())
+                    .unwrap_or_default();
+                let active = if terminal.active { "*" } else { "" };
+                println!("{}{active}\t{}\t{directory}", terminal.id, terminal.title);
+            }
+            glib::ExitCode::SUCCESS
+        }
+        Ok(Response::Error { message }) => {
+            eprintln!("{message}.");
+            glib::ExitCode::FAILURE
+        }
+        Err(error) => {
+            eprintln!("Failed to send request: {error}.");
+            glib::ExitCode::FAILURE
+        }
+    }
 }
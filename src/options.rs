@@ -1,7 +1,8 @@
-use clap::Parser;
-use std::path::PathBuf;
+use crate::{control::Request, shell_integration::Shell};
+use clap::{Parser, Subcommand};
+use std::{ffi::OsString, path::PathBuf};
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Default, Parser)]
 #[command(version, about, long_about = None)]
 pub struct Options {
     #[arg(long, default_value_t = false, help = "Use the default config")]
@@ -9,6 +10,66 @@ pub struct Options {
 
     #[arg(long, help = "Use a custom configuration directory")]
     pub config_path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Open windows in the running instance"
+    )]
+    pub single_instance: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Show or hide the drop-down window of the running instance. Implies \
+                --single-instance"
+    )]
+    pub dropdown: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Record the session to an asciicast file"
+    )]
+    pub record: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    #[command(about = "Print the shell integration script for a shell")]
+    ShellIntegration {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    #[command(about = "Control a running instance")]
+    Msg {
+        #[arg(long, help = "The socket of the instance")]
+        socket: Option<PathBuf>,
+
+        #[command(subcommand)]
+        request: Request,
+    },
+
+    #[command(about = "Play back an asciicast recording")]
+    Play {
+        #[arg(help = "The recording to play")]
+        file: PathBuf,
+
+        #[arg(long, default_value_t = 1.0, value_parser = positive, help = "Playback speed")]
+        speed: f64,
+
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            value_parser = positive,
+            help = "Shorten pauses to at most this long"
+        )]
+        idle_time_limit: Option<f64>,
+    },
 }
 
 impl Options {
@@ -17,4 +78,25 @@ impl Options {
         tracing::debug!("Parsed options `{options:?}`.");
         options
     }
+
+    /// Parse the arguments of an invocation forwarded to the running
+    /// instance.
+    pub fn try_parse_from<I, T>(arguments: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let options = <Self as Parser>::try_parse_from(arguments)?;
+        tracing::debug!("Parsed forwarded options `{options:?}`.");
+        Ok(options)
+    }
+}
+
+/// Parse a number that is finite and greater than zero.
+fn positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        Ok(_) => Err("must be a finite number greater than zero".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
 }
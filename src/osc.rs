@@ -0,0 +1,471 @@
+//! Scanner for operating system command (OSC) sequences in the output of the
+//! child process. The output itself is passed on to VTE unchanged, the scanner
+//! only reports sequences that Galactic handles in addition to VTE. A few
+//! control sequences (CSI) are reported as well.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+const BEL: u8 = 0x07;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1a;
+const ESC: u8 = 0x1b;
+
+/// Sequences exceeding this length are dropped.
+const MAX_PAYLOAD_LENGTH: usize = 1 << 20;
+
+/// The longest clipboard text whose OSC 52 sequence is not dropped, leaving
+/// room for the selections in front of the base64 encoded text.
+pub const MAX_CLIPBOARD_SIZE: usize = (MAX_PAYLOAD_LENGTH - 64) / 4 * 3;
+
+/// Control sequences exceeding this length are dropped.
+const MAX_CSI_LENGTH: usize = 32;
+
+/// Bracketed paste mode.
+pub const BRACKETED_PASTE: u16 = 2004;
+
+/// Mouse tracking modes for clicks, drags and any motion.
+pub const MOUSE_TRACKING: [u16; 4] = [1000, 1001, 1002, 1003];
+
+#[derive(Debug, Default)]
+pub struct Scanner {
+    state: State,
+    payload: Vec<u8>,
+    overflow: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sequence {
+    SemanticPrompt(Mark),
+    CursorStyle(u8),
+    PrivateModes {
+        modes: Vec<u16>,
+        enabled: bool,
+    },
+    Notification {
+        title: Option<String>,
+        body: String,
+    },
+    SetClipboard {
+        selections: Vec<Selection>,
+        text: String,
+    },
+    QueryClipboard(Selection),
+}
+
+/// The selections that can be accessed with OSC 52.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+}
+
+/// Semantic prompt marks as defined by FinalTerm (OSC 133).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    PromptStart,
+    CommandStart,
+    CommandExecuted,
+    CommandFinished(Option<i32>),
+}
+
+impl Scanner {
+    /// Scan a chunk of output. Sequences may span multiple chunks. Returns the
+    /// recognized sequences together with the offset directly after their
+    /// terminator in `data`.
+    pub fn scan(&mut self, data: &[u8]) -> Vec<(usize, Sequence)> {
+        let mut sequences = Vec::new();
+
+        for (index, &byte) in data.iter().enumerate() {
+            match (self.state, byte) {
+                (State::Ground, ESC) => self.state = State::Escape,
+                (State::Ground, _) => {}
+                (State::Escape, b']') => self.begin(),
+                (State::Escape, b'[') => {
+                    self.state = State::Csi;
+                    self.payload.clear();
+                    self.overflow = false;
+                }
+                (State::Escape, ESC) => {}
+                (State::Escape, _) => self.state = State::Ground,
+                (State::Csi, 0x20..=0x3f) => {
+                    if self.payload.len() < MAX_CSI_LENGTH {
+                        self.payload.push(byte);
+                    } else {
+                        self.overflow = true;
+                    }
+                }
+                (State::Csi, 0x40..=0x7e) => {
+                    if let Some(sequence) = self.finish_csi(byte) {
+                        sequences.push((index + 1, sequence));
+                    }
+                }
+                (State::Csi, ESC) => self.state = State::Escape,
+                (State::Csi, CAN | SUB) => self.state = State::Ground,
+                (State::Csi, _) => {}
+                (State::Osc, BEL) => {
+                    if let Some(sequence) = self.finish() {
+                        sequences.push((index + 1, sequence));
+                    }
+                }
+                (State::Osc, ESC) => self.state = State::OscEscape,
+                (State::Osc, CAN | SUB) => self.state = State::Ground,
+                (State::Osc, _) => {
+                    if self.payload.len() < MAX_PAYLOAD_LENGTH {
+                        self.payload.push(byte);
+                    } else {
+                        self.overflow = true;
+                    }
+                }
+                (State::OscEscape, b'\\') => {
+                    if let Some(sequence) = self.finish() {
+                        sequences.push((index + 1, sequence));
+                    }
+                }
+                (State::OscEscape, b']') => self.begin(),
+                (State::OscEscape, ESC) => self.state = State::Escape,
+                (State::OscEscape, _) => self.state = State::Ground,
+            }
+        }
+
+        sequences
+    }
+
+    fn begin(&mut self) {
+        self.state = State::Osc;
+        self.payload.clear();
+        self.overflow = false;
+    }
+
+    fn finish(&mut self) -> Option<Sequence> {
+        self.state = State::Ground;
+        if self.overflow {
+            tracing::warn!("Drop sequence exceeding {MAX_PAYLOAD_LENGTH} bytes.");
+            return None;
+        }
+
+        let sequence = Sequence::parse(&self.payload);
+        tracing::trace!("Scanned sequence `{sequence:?}`.");
+        sequence
+    }
+
+    fn finish_csi(&mut self, final_byte: u8) -> Option<Sequence> {
+        self.state = State::Ground;
+        if self.overflow {
+            return None;
+        }
+
+        match (self.payload.as_slice(), final_byte) {
+            // Set cursor style (DECSCUSR).
+            ([parameter @ .., b' '], b'q') => {
+                let parameter = std::str::from_utf8(parameter).ok()?;
+                let style = if parameter.is_empty() {
+                    0
+                } else {
+                    parameter.parse().ok()?
+                };
+                Some(Sequence::CursorStyle(style))
+            }
+            // Enable or disable private modes (DECSET and DECRST).
+            ([b'?', parameters @ ..], b'h' | b'l') => {
+                let parameters = std::str::from_utf8(parameters).ok()?;
+                let modes: Vec<u16> = parameters
+                    .split(';')
+                    .filter_map(|parameter| parameter.parse().ok())
+                    .filter(|mode| *mode == BRACKETED_PASTE || MOUSE_TRACKING.contains(mode))
+                    .collect();
+                (!modes.is_empty()).then_some(Sequence::PrivateModes {
+                    modes,
+                    enabled: final_byte == b'h',
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Sequence {
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let payload = std::str::from_utf8(payload).ok()?;
+        let (code, arguments) = payload.split_once(';').unwrap_or((payload, ""));
+        match code {
+            "133" => Mark::parse(arguments).map(Self::SemanticPrompt),
+            "777" => {
+                let (kind, arguments) = arguments.split_once(';')?;
+                if kind != "notify" {
+                    return None;
+                }
+                let (title, body) = arguments.split_once(';').unwrap_or((arguments, ""));
+                Some(Self::Notification {
+                    title: Some(title.to_string()),
+                    body: body.to_string(),
+                })
+            }
+            "52" => {
+                let (selections, data) = arguments.split_once(';')?;
+                let selections = Selection::parse(selections);
+                if data == "?" {
+                    return Some(Self::QueryClipboard(selections[0]));
+                }
+                let text = String::from_utf8(STANDARD.decode(data).ok()?).ok()?;
+                Some(Self::SetClipboard { selections, text })
+            }
+            // ConEmu uses OSC 9 with numeric subcommands for other purposes.
+            "9" if !Self::is_subcommand(arguments) => Some(Self::Notification {
+                title: None,
+                body: arguments.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn is_subcommand(arguments: &str) -> bool {
+        let code = arguments.split(';').next().unwrap_or_default();
+        !code.is_empty() && code.bytes().all(|byte| byte.is_ascii_digit())
+    }
+}
+
+impl Selection {
+    /// Parse the selection parameter of OSC 52. The select and cut buffer
+    /// selections of xterm fall back to the clipboard, as does an empty
+    /// parameter.
+    fn parse(parameter: &str) -> Vec<Self> {
+        let mut selections = Vec::new();
+        if parameter.contains(['c', 's', '0', '1', '2', '3', '4', '5', '6', '7']) {
+            selections.push(Self::Clipboard);
+        }
+        if parameter.contains('p') {
+            selections.push(Self::Primary);
+        }
+        if selections.is_empty() {
+            selections.push(Self::Clipboard);
+        }
+        selections
+    }
+
+    /// The parameter used to answer a query.
+    pub fn parameter(&self) -> char {
+        match self {
+            Self::Clipboard => 'c',
+            Self::Primary => 'p',
+        }
+    }
+}
+
+/// The answer to an OSC 52 query with the contents of `selection`.
+pub fn clipboard_reply(selection: Selection, text: &str) -> String {
+    format!(
+        "\x1b]52;{};{}\x07",
+        selection.parameter(),
+        STANDARD.encode(text)
+    )
+}
+
+impl Mark {
+    fn parse(arguments: &str) -> Option<Self> {
+        let mut arguments = arguments.split(';');
+        match arguments.next()? {
+            "A" => Some(Self::PromptStart),
+            "B" => Some(Self::CommandStart),
+            "C" => Some(Self::CommandExecuted),
+            "D" => Some(Self::CommandFinished(
+                arguments.next().and_then(|code| code.parse().ok()),
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(data: &[u8]) -> Vec<Sequence> {
+        Scanner::default()
+            .scan(data)
+            .into_iter()
+            .map(|(_, sequence)| sequence)
+            .collect()
+    }
+
+    #[test]
+    fn terminators() {
+        let mark = [Sequence::SemanticPrompt(Mark::PromptStart)];
+        assert_eq!(scan(b"\x1b]133;A\x07"), mark);
+        assert_eq!(scan(b"\x1b]133;A\x1b\\"), mark);
+        assert_eq!(scan(b"\x1b]133;A"), []);
+        assert_eq!(scan(b"\x1b]133;A\x18\x07"), []);
+        assert_eq!(
+            scan(b"\x1b]133;A\x1b]133;B\x07"),
+            [Sequence::SemanticPrompt(Mark::CommandStart)]
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let sequences = Scanner::default().scan(b"a\x1b]133;A\x07b\x1b]133;D;1\x1b\\c");
+        assert_eq!(
+            sequences,
+            [
+                (9, Sequence::SemanticPrompt(Mark::PromptStart)),
+                (21, Sequence::SemanticPrompt(Mark::CommandFinished(Some(1)))),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_across_chunks() {
+        let mut scanner = Scanner::default();
+        assert_eq!(scanner.scan(b"a\x1b"), []);
+        assert_eq!(scanner.scan(b"]13"), []);
+        assert_eq!(scanner.scan(b"3;D;0\x1b"), []);
+        assert_eq!(
+            scanner.scan(b"\\b"),
+            [(1, Sequence::SemanticPrompt(Mark::CommandFinished(Some(0))))]
+        );
+
+        assert_eq!(scanner.scan(b"\x1b[?20"), []);
+        assert_eq!(
+            scanner.scan(b"04h"),
+            [(
+                3,
+                Sequence::PrivateModes {
+                    modes: vec![BRACKETED_PASTE],
+                    enabled: true
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn clipboard() {
+        assert_eq!(
+            scan(b"\x1b]52;c;aGVsbG8=\x07"),
+            [Sequence::SetClipboard {
+                selections: vec![Selection::Clipboard],
+                text: "hello".to_string()
+            }]
+        );
+        assert_eq!(
+            scan(b"\x1b]52;pc;\x07"),
+            [Sequence::SetClipboard {
+                selections: vec![Selection::Clipboard, Selection::Primary],
+                text: String::new()
+            }]
+        );
+        assert_eq!(
+            scan(b"\x1b]52;;?\x07"),
+            [Sequence::QueryClipboard(Selection::Clipboard)]
+        );
+        assert_eq!(
+            scan(b"\x1b]52;p;?\x1b\\"),
+            [Sequence::QueryClipboard(Selection::Primary)]
+        );
+    }
+
+    #[test]
+    fn clipboard_invalid() {
+        // Not base64, missing padding and not UTF-8 once decoded.
+        assert_eq!(scan(b"\x1b]52;c;a*b=\x07"), []);
+        assert_eq!(scan(b"\x1b]52;c;aGVsbG8\x07"), []);
+        assert_eq!(scan(b"\x1b]52;c;/w==\x07"), []);
+        assert_eq!(scan(b"\x1b]52;c\x07"), []);
+    }
+
+    #[test]
+    fn clipboard_size() {
+        // The selections take at most 60 bytes of the room left for them.
+        let text = "a".repeat(MAX_CLIPBOARD_SIZE);
+        let sequence = |selections: &str| {
+            format!("\x1b]52;{selections};{}\x07", STANDARD.encode(&text)).into_bytes()
+        };
+
+        let sequences = scan(&sequence(&"c".repeat(60)));
+        assert!(matches!(
+            sequences.as_slice(),
+            [Sequence::SetClipboard { text: scanned, .. }] if *scanned == text
+        ));
+        assert_eq!(scan(&sequence(&"c".repeat(61))), []);
+
+        // Sequences after a dropped one are scanned again.
+        let mut scanner = Scanner::default();
+        scanner.scan(&sequence(&"c".repeat(61)));
+        assert_eq!(
+            scanner.scan(b"\x1b]52;c;?\x07"),
+            [(9, Sequence::QueryClipboard(Selection::Clipboard))]
+        );
+    }
+
+    #[test]
+    fn clipboard_reply_round_trip() {
+        let reply = clipboard_reply(Selection::Primary, "hello");
+        assert_eq!(reply, "\x1b]52;p;aGVsbG8=\x07");
+        assert_eq!(
+            scan(reply.as_bytes()),
+            [Sequence::SetClipboard {
+                selections: vec![Selection::Primary],
+                text: "hello".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn notifications() {
+        assert_eq!(
+            scan(b"\x1b]777;notify;Title;Body;more\x07"),
+            [Sequence::Notification {
+                title: Some("Title".to_string()),
+                body: "Body;more".to_string()
+            }]
+        );
+        assert_eq!(
+            scan(b"\x1b]9;Done\x1b\\"),
+            [Sequence::Notification {
+                title: None,
+                body: "Done".to_string()
+            }]
+        );
+        // ConEmu progress and other OSC 777 commands.
+        assert_eq!(scan(b"\x1b]9;4;1;50\x07"), []);
+        assert_eq!(scan(b"\x1b]777;preexec\x07"), []);
+    }
+
+    #[test]
+    fn cursor_style() {
+        assert_eq!(scan(b"\x1b[5 q"), [Sequence::CursorStyle(5)]);
+        assert_eq!(scan(b"\x1b[ q"), [Sequence::CursorStyle(0)]);
+        assert_eq!(scan(b"\x1b[x q"), []);
+        assert_eq!(scan(b"\x1b[5q"), []);
+    }
+
+    #[test]
+    fn private_modes() {
+        assert_eq!(
+            scan(b"\x1b[?25;2004;1000h"),
+            [Sequence::PrivateModes {
+                modes: vec![BRACKETED_PASTE, 1000],
+                enabled: true
+            }]
+        );
+        assert_eq!(
+            scan(b"\x1b[?1002;1003l"),
+            [Sequence::PrivateModes {
+                modes: vec![1002, 1003],
+                enabled: false
+            }]
+        );
+        assert_eq!(scan(b"\x1b[?25h"), []);
+        assert_eq!(scan(b"\x1b[2004h"), []);
+        let long = format!("\x1b[?{}2004h", "0".repeat(MAX_CSI_LENGTH));
+        assert_eq!(scan(long.as_bytes()), []);
+    }
+}
@@ -0,0 +1,160 @@
+//! Checks and filters for text pasted into the terminal. Without bracketed
+//! paste, the shell cannot tell pasted text from typed text, so a pasted
+//! newline runs a command right away.
+
+use crate::config::clipboard::Clipboard;
+
+/// Pastes longer than this are cut off in the preview.
+const MAX_PREVIEW_LENGTH: usize = 2000;
+
+/// Sequences that start and end bracketed paste. Pasted text must not
+/// contain them, or it could end bracketed paste early and run commands.
+const BRACKETED_PASTE_MARKERS: [&str; 2] = ["\x1b[200~", "\x1b[201~"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    Newline,
+    Sudo,
+    ControlCharacters,
+}
+
+impl Warning {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Newline => "The text contains line breaks, which run commands right away.",
+            Self::Sudo => "The text contains commands run with sudo.",
+            Self::ControlCharacters => "The text contains control characters.",
+        }
+    }
+}
+
+/// Apply the paste options of the config to `text` and remove bracketed
+/// paste markers.
+pub fn filter(text: &str, config: &Clipboard) -> String {
+    let mut text = text.to_string();
+    while let Some(marker) = BRACKETED_PASTE_MARKERS
+        .iter()
+        .find(|marker| text.contains(**marker))
+    {
+        text = text.replace(marker, "");
+    }
+    if config.strip_trailing_newline {
+        let length = text.trim_end_matches(['\n', '\r']).len();
+        text.truncate(length);
+    }
+    if let Some(width) = config.convert_tabs {
+        text = text.replace('\t', &" ".repeat(width as usize));
+    }
+    text
+}
+
+/// The reasons to confirm pasting `text`.
+pub fn check(text: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if text.contains(['\n', '\r']) {
+        warnings.push(Warning::Newline);
+    }
+    let is_separator = |c: char| c.is_whitespace() || ";&|()`$".contains(c);
+    if text.split(is_separator).any(|word| word == "sudo") {
+        warnings.push(Warning::Sudo);
+    }
+    if text.chars().any(is_unsafe_control) {
+        warnings.push(Warning::ControlCharacters);
+    }
+    warnings
+}
+
+/// The text to show in the confirmation dialog, with control characters
+/// replaced by visible symbols.
+pub fn preview(text: &str) -> String {
+    let mut preview: String = text
+        .chars()
+        .take(MAX_PREVIEW_LENGTH)
+        .map(|c| match c as u32 {
+            // Control pictures start at U+2400 for NUL.
+            code @ 0x00..=0x1f if is_unsafe_control(c) => char::from_u32(0x2400 + code).unwrap(),
+            0x7f => '\u{2421}',
+            _ if is_unsafe_control(c) => '\u{fffd}',
+            _ => c,
+        })
+        .collect();
+    if text.chars().count() > MAX_PREVIEW_LENGTH {
+        preview.push('…');
+    }
+    preview
+}
+
+fn is_unsafe_control(c: char) -> bool {
+    c.is_control() && !matches!(c, '\n' | '\r' | '\t')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(strip_trailing_newline: bool, convert_tabs: Option<u32>) -> Clipboard {
+        Clipboard {
+            strip_trailing_newline,
+            convert_tabs,
+            ..Clipboard::default()
+        }
+    }
+
+    #[test]
+    fn filter_trailing_newline() {
+        assert_eq!(filter("ls\n", &config(false, None)), "ls\n");
+        assert_eq!(filter("ls\r\n\n", &config(true, None)), "ls");
+        assert_eq!(filter("ls\na\n", &config(true, None)), "ls\na");
+    }
+
+    #[test]
+    fn filter_tabs() {
+        assert_eq!(filter("a\tb", &config(false, None)), "a\tb");
+        assert_eq!(filter("a\tb", &config(false, Some(2))), "a  b");
+    }
+
+    #[test]
+    fn filter_bracketed_paste_markers() {
+        let config = config(false, None);
+        assert_eq!(filter("a\x1b[201~rm -rf ~\n", &config), "arm -rf ~\n");
+        assert_eq!(filter("\x1b[200~a\x1b[201~", &config), "a");
+        // Removing a marker must not leave a new one behind.
+        assert_eq!(filter("\x1b[20\x1b[201~1~", &config), "");
+        assert_eq!(filter("\x1b[202~", &config), "\x1b[202~");
+    }
+
+    #[test]
+    fn check_newlines() {
+        assert_eq!(check("ls"), []);
+        assert_eq!(check("ls\n"), [Warning::Newline]);
+        assert_eq!(check("ls\rrm"), [Warning::Newline]);
+        assert_eq!(check("a\r\nb"), [Warning::Newline]);
+    }
+
+    #[test]
+    fn check_sudo() {
+        assert_eq!(check("sudo rm"), [Warning::Sudo]);
+        assert_eq!(check("ls;sudo rm"), [Warning::Sudo]);
+        assert_eq!(check("$(sudo rm)"), [Warning::Sudo]);
+        assert_eq!(check("echo pseudo sudoers"), []);
+        assert_eq!(check("sudo\n"), [Warning::Newline, Warning::Sudo]);
+    }
+
+    #[test]
+    fn check_control_characters() {
+        assert_eq!(check("a\tb"), []);
+        assert_eq!(check("a\x1bb"), [Warning::ControlCharacters]);
+        assert_eq!(check("a\x03"), [Warning::ControlCharacters]);
+        assert_eq!(check("a\x7f"), [Warning::ControlCharacters]);
+        assert_eq!(check("a\u{9b}201~"), [Warning::ControlCharacters]);
+        assert_eq!(check("\x1b[201~"), [Warning::ControlCharacters]);
+    }
+
+    #[test]
+    fn preview_control_characters() {
+        assert_eq!(
+            preview("a\x1b[201~\x7f\u{9b}\n"),
+            "a\u{241b}[201~\u{2421}\u{fffd}\n"
+        );
+    }
+}
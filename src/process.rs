@@ -0,0 +1,98 @@
+//! Inspection of the processes running in a terminal.
+
+use std::{fs, os::fd::RawFd};
+
+/// The names of the processes in the foreground process group of the
+/// pseudo terminal `fd`, unless the shell with the process ID `shell` is in
+/// the foreground itself.
+pub fn foreground_processes(fd: RawFd, shell: i32) -> Vec<String> {
+    let group = unsafe { libc::tcgetpgrp(fd) };
+    if group <= 0 || group == shell {
+        return Vec::new();
+    }
+
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut processes: Vec<(i32, String)> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok())
+        .filter_map(|pid| {
+            let (name, process_group) = stat(pid)?;
+            (process_group == group).then_some((pid, name))
+        })
+        .collect();
+    processes.sort();
+    processes.into_iter().map(|(_, name)| name).collect()
+}
+
+/// The name and process group of a process. The name is enclosed in
+/// parentheses and may contain any character, so the fields after it are
+/// found from the last closing parenthesis.
+fn stat(pid: i32) -> Option<(String, i32)> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let start = stat.find('(')?;
+    let end = stat.rfind(')')?;
+    let name = stat.get(start + 1..end)?.to_string();
+
+    // The fields after the name are the state, the parent and the group.
+    let group = stat[end + 1..].split_whitespace().nth(2)?.parse().ok()?;
+    Some((name, group))
+}
+
+/// How a child process ended, decoded from its wait status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Code(i32),
+    Signal(i32),
+}
+
+impl ExitStatus {
+    pub fn from_wait_status(status: i32) -> Self {
+        if libc::WIFSIGNALED(status) {
+            Self::Signal(libc::WTERMSIG(status))
+        } else {
+            Self::Code(libc::WEXITSTATUS(status))
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        *self == Self::Code(0)
+    }
+}
+
+impl std::fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Code(code) => write!(f, "exit code {code}"),
+            Self::Signal(signal) => match signal_name(*signal) {
+                Some(name) => write!(f, "signal {name}"),
+                None => write!(f, "signal {signal}"),
+            },
+        }
+    }
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGSYS => "SIGSYS",
+        _ => return None,
+    };
+    Some(name)
+}
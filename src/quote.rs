@@ -0,0 +1,172 @@
+//! Quoting of text for POSIX shells, so it is read back as a single word.
+
+use crate::config::drag_and_drop::Quoting;
+use std::path::Path;
+
+/// Whether `c` needs no quoting in a word, `first` telling whether it starts
+/// the word. zsh replaces a word starting with `=` by the path of a command.
+fn is_safe(c: char, first: bool) -> bool {
+    (c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c)) && !(first && c == '=')
+}
+
+/// Quote `text` in the given style. Text made of safe characters only is left
+/// as it is.
+pub fn quote(text: &str, quoting: Quoting) -> String {
+    let safe = text
+        .chars()
+        .enumerate()
+        .all(|(index, c)| is_safe(c, index == 0));
+    if !text.is_empty() && safe {
+        return text.to_string();
+    }
+
+    match quoting {
+        Quoting::Single => format!("'{}'", text.replace('\'', r"'\''")),
+        Quoting::Double => {
+            let mut quoted = String::from('"');
+            for c in text.chars() {
+                match c {
+                    // A backslash before `!` would stop history expansion
+                    // but stay in the word, so `!` goes in single quotes.
+                    '!' => quoted.push_str(r#""'!'""#),
+                    '"' | '\\' | '$' | '`' => {
+                        quoted.push('\\');
+                        quoted.push(c);
+                    }
+                    c => quoted.push(c),
+                }
+            }
+            quoted.push('"');
+            quoted
+        }
+        Quoting::Backslash if text.is_empty() => "''".to_string(),
+        Quoting::Backslash => {
+            let mut quoted = String::new();
+            for (index, c) in text.chars().enumerate() {
+                match c {
+                    // A backslash before a line break continues the line
+                    // instead of escaping it.
+                    '\n' => quoted.push_str("'\n'"),
+                    c if is_safe(c, index == 0) || !c.is_ascii() => quoted.push(c),
+                    c => {
+                        quoted.push('\\');
+                        quoted.push(c);
+                    }
+                }
+            }
+            quoted
+        }
+    }
+}
+
+/// Quote the path of a file. Paths that are not valid UTF-8 are quoted as the
+/// `uri` of the file instead, so no part of them is lost.
+pub fn quote_path(path: &Path, uri: &str, quoting: Quoting) -> String {
+    match path.to_str() {
+        Some(path) => quote(path, quoting),
+        None => quote(uri, quoting),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    fn quote_all(text: &str) -> [String; 3] {
+        [Quoting::Single, Quoting::Double, Quoting::Backslash].map(|quoting| quote(text, quoting))
+    }
+
+    #[test]
+    fn safe_text() {
+        assert_eq!(quote_all("/tmp/a-b_c.txt"), ["/tmp/a-b_c.txt"; 3]);
+        assert_eq!(quote_all("a=b,c:d@e%f+g"), ["a=b,c:d@e%f+g"; 3]);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(quote_all(""), ["''", r#""""#, "''"]);
+    }
+
+    #[test]
+    fn spaces() {
+        assert_eq!(
+            quote_all("a b\tc"),
+            ["'a b\tc'", "\"a b\tc\"", "a\\ b\\\tc"]
+        );
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(
+            quote_all(r#"it's "x""#),
+            [r#"'it'\''s "x"'"#, r#""it's \"x\"""#, r#"it\'s\ \"x\""#]
+        );
+    }
+
+    #[test]
+    fn expansions() {
+        assert_eq!(
+            quote_all("$HOME`id`$(id)"),
+            [
+                "'$HOME`id`$(id)'",
+                r#""\$HOME\`id\`\$(id)""#,
+                r"\$HOME\`id\`\$\(id\)"
+            ]
+        );
+        assert_eq!(
+            quote_all("*?[a]{b,c}"),
+            ["'*?[a]{b,c}'", r#""*?[a]{b,c}""#, r"\*\?\[a\]\{b,c\}"]
+        );
+    }
+
+    #[test]
+    fn history_expansion() {
+        assert_eq!(
+            quote_all("a!!b"),
+            ["'a!!b'", r#""a"'!'""'!'"b""#, r"a\!\!b"]
+        );
+    }
+
+    #[test]
+    fn backslashes() {
+        assert_eq!(quote_all(r"a\b"), [r"'a\b'", r#""a\\b""#, r"a\\b"]);
+    }
+
+    #[test]
+    fn line_breaks() {
+        assert_eq!(
+            quote_all("a\nb;c&d|e"),
+            ["'a\nb;c&d|e'", "\"a\nb;c&d|e\"", "a'\n'b\\;c\\&d\\|e"]
+        );
+    }
+
+    #[test]
+    fn leading_characters() {
+        // Quoting cannot keep a word from being taken for an option.
+        assert_eq!(quote_all("-rf"), ["-rf"; 3]);
+        assert_eq!(quote_all("~/a"), ["'~/a'", r#""~/a""#, r"\~/a"]);
+        assert_eq!(quote_all("=ls"), ["'=ls'", r#""=ls""#, r"\=ls"]);
+        assert_eq!(quote_all("a~=b"), ["'a~=b'", r#""a~=b""#, r"a\~=b"]);
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!(quote_all("日本"), ["'日本'", "\"日本\"", "日本"]);
+    }
+
+    #[test]
+    fn paths() {
+        let path = Path::new("/tmp/a b");
+        assert_eq!(
+            quote_path(path, "file:///tmp/a%20b", Quoting::Single),
+            "'/tmp/a b'"
+        );
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/a\xff b"));
+        assert_eq!(
+            quote_path(path, "file:///tmp/a%FF%20b", Quoting::Single),
+            "file:///tmp/a%FF%20b"
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Recording and loading of terminal sessions in the asciicast v2 format.
+//!
+//! A recording starts with a header line describing the terminal, followed by
+//! one line per event with the time since the start of the recording. See
+//! <https://docs.asciinema.org/manual/asciicast/v2/> for the format.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Serialize)]
+struct Header<'a> {
+    version: u8,
+    width: u32,
+    height: u32,
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    env: Environment,
+}
+
+#[derive(Debug, Serialize)]
+struct Environment {
+    #[serde(rename = "TERM")]
+    term: String,
+    #[serde(rename = "SHELL", skip_serializing_if = "Option::is_none")]
+    shell: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoadedHeader {
+    version: u8,
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    idle_time_limit: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub time: f64,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum EventKind {
+    Output(String),
+    Resize(u32, u32),
+}
+
+/// A recording loaded for playback. Input and marker events are left out.
+#[derive(Debug, Clone, Default)]
+pub struct Cast {
+    pub width: u32,
+    pub height: u32,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug)]
+pub struct Recorder {
+    file: File,
+    start: Instant,
+    output: Utf8Buffer,
+    input: Utf8Buffer,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, width: u32, height: u32, title: Option<&str>) -> io::Result<Self> {
+        tracing::info!("Start recording to {path:?}.");
+        let mut file = File::create(path)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let header = Header {
+            version: 2,
+            width,
+            height,
+            timestamp,
+            title,
+            env: Environment {
+                term: "xterm-256color".to_string(),
+                shell: std::env::var("SHELL").ok(),
+            },
+        };
+        let mut line = serde_json::to_string(&header)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+
+        Ok(Self {
+            file,
+            start: Instant::now(),
+            output: Utf8Buffer::default(),
+            input: Utf8Buffer::default(),
+        })
+    }
+
+    /// Record output of the child process.
+    pub fn output(&mut self, data: &[u8]) -> io::Result<()> {
+        let text = self.output.decode(data);
+        self.event("o", &text)
+    }
+
+    /// Record input to the child process.
+    pub fn input(&mut self, data: &[u8]) -> io::Result<()> {
+        let text = self.input.decode(data);
+        self.event("i", &text)
+    }
+
+    /// Record a change of the terminal size.
+    pub fn resize(&mut self, width: u32, height: u32) -> io::Result<()> {
+        self.event("r", &format!("{width}x{height}"))
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        // Each event is written at once, so the recording stays readable up
+        // to the last event if the application stops unexpectedly.
+        let time = self.start.elapsed().as_secs_f64();
+        let mut line = serde_json::to_string(&(time, code, data))?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())
+    }
+}
+
+/// Decodes a byte stream as UTF-8. Characters split across reads are held
+/// back until the rest of them arrives.
+#[derive(Debug, Default)]
+struct Utf8Buffer {
+    pending: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    fn decode(&mut self, data: &[u8]) -> String {
+        self.pending.extend_from_slice(data);
+        let length = match std::str::from_utf8(&self.pending) {
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            _ => self.pending.len(),
+        };
+        let text = String::from_utf8_lossy(&self.pending[..length]).into_owned();
+        self.pending.drain(..length);
+        text
+    }
+}
+
+impl Cast {
+    /// Load a recording. Pauses between events are shortened to
+    /// `idle_time_limit` seconds, falling back to the limit stored in the
+    /// recording.
+    pub fn load(path: &Path, idle_time_limit: Option<f64>) -> io::Result<Self> {
+        tracing::info!("Load recording from {path:?}.");
+        Self::parse(&fs::read_to_string(path)?, idle_time_limit)
+    }
+
+    fn parse(contents: &str, idle_time_limit: Option<f64>) -> io::Result<Self> {
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let header: LoadedHeader = match lines.next() {
+            Some((_, line)) => serde_json::from_str(line)?,
+            None => return Err(invalid_data("The recording is empty".to_string())),
+        };
+        if header.version != 2 {
+            return Err(invalid_data(format!(
+                "Unsupported asciicast version {}",
+                header.version
+            )));
+        }
+        let idle_time_limit = idle_time_limit.or(header.idle_time_limit);
+
+        let mut events = Vec::new();
+        let (mut previous, mut shift) = (0.0, 0.0);
+        for (index, line) in lines {
+            let (time, code, data): (f64, String, String) =
+                serde_json::from_str(line).map_err(|error| {
+                    invalid_data(format!("Invalid event on line {}: {error}", index + 1))
+                })?;
+
+            // Events are played in the order they are stored, so events
+            // going back in time are played right after the previous one.
+            let time = time.max(previous);
+            if let Some(limit) = idle_time_limit {
+                let pause = time - previous;
+                if pause > limit {
+                    shift += pause - limit;
+                }
+            }
+            previous = time;
+
+            let kind = match code.as_str() {
+                "o" => EventKind::Output(data),
+                "r" => match parse_size(&data) {
+                    Some((width, height)) => EventKind::Resize(width, height),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            events.push(Event {
+                time: time - shift,
+                kind,
+            });
+        }
+
+        Ok(Self {
+            width: header.width,
+            height: header.height,
+            events,
+        })
+    }
+
+    /// The time of the last event in seconds.
+    pub fn duration(&self) -> f64 {
+        self.events
+            .last()
+            .map(|event| event.time)
+            .unwrap_or_default()
+    }
+}
+
+/// Parse a size in the `<width>x<height>` format of resize events.
+fn parse_size(data: &str) -> Option<(u32, u32)> {
+    let (width, height) = data.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_split_characters() {
+        let mut buffer = Utf8Buffer::default();
+        let bytes = "aé€😀".as_bytes();
+        assert_eq!(buffer.decode(&bytes[..2]), "a");
+        assert_eq!(buffer.decode(&bytes[2..4]), "é");
+        assert_eq!(buffer.decode(&bytes[4..5]), "");
+        assert_eq!(buffer.decode(&bytes[5..6]), "€");
+        assert_eq!(buffer.decode(&bytes[6..7]), "");
+        assert_eq!(buffer.decode(&bytes[7..9]), "");
+        assert_eq!(buffer.decode(&bytes[9..]), "😀");
+    }
+
+    #[test]
+    fn utf8_invalid_bytes() {
+        let mut buffer = Utf8Buffer::default();
+        assert_eq!(buffer.decode(b"a\xffb"), "a\u{fffd}b");
+        assert_eq!(buffer.decode(b"\xe2\x82"), "");
+        assert_eq!(buffer.decode(b"x"), "\u{fffd}x");
+    }
+
+    fn times(cast: &Cast) -> Vec<f64> {
+        cast.events.iter().map(|event| event.time).collect()
+    }
+
+    #[test]
+    fn load_events() {
+        let cast = Cast::parse(
+            "{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+             [0.5, \"o\", \"a\"]\n\
+             [1.0, \"i\", \"b\"]\n\
+             \n\
+             [1.5, \"r\", \"100x30\"]\n\
+             [2.0, \"r\", \"invalid\"]\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!((cast.width, cast.height), (80, 24));
+        assert_eq!(times(&cast), [0.5, 1.5]);
+        assert!(matches!(&cast.events[0].kind, EventKind::Output(text) if text == "a"));
+        assert!(matches!(cast.events[1].kind, EventKind::Resize(100, 30)));
+    }
+
+    #[test]
+    fn load_malformed() {
+        let error = |contents| Cast::parse(contents, None).unwrap_err().kind();
+        assert_eq!(error(""), io::ErrorKind::InvalidData);
+        assert_eq!(error("{\"width\": 80}"), io::ErrorKind::InvalidData);
+        assert_eq!(error("not json"), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error("{\"version\": 1, \"width\": 80, \"height\": 24}"),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            error("{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\"]"),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn load_out_of_order() {
+        let cast = Cast::parse(
+            "{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+             [2.0, \"o\", \"a\"]\n\
+             [1.0, \"o\", \"b\"]\n\
+             [-1.0, \"o\", \"c\"]\n\
+             [3.0, \"o\", \"d\"]\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(times(&cast), [2.0, 2.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn load_idle_time_limit() {
+        let contents =
+            "{\"version\": 2, \"width\": 80, \"height\": 24, \"idle_time_limit\": 2.0}\n\
+             [1.0, \"o\", \"a\"]\n\
+             [10.0, \"i\", \"b\"]\n\
+             [11.0, \"o\", \"c\"]\n\
+             [20.0, \"o\", \"d\"]\n";
+        assert_eq!(
+            times(&Cast::parse(contents, None).unwrap()),
+            [1.0, 4.0, 6.0]
+        );
+        assert_eq!(
+            times(&Cast::parse(contents, Some(0.5)).unwrap()),
+            [0.5, 1.5, 2.0]
+        );
+    }
+}
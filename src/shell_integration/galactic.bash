@@ -0,0 +1,37 @@
+# Galactic shell integration for bash.
+#
+# Add the following line to the end of `~/.bashrc`:
+#
+#     eval "$(galactic shell-integration bash)"
+
+if [[ $- == *i* && -z "$GALACTIC_SHELL_INTEGRATION" ]]; then
+    GALACTIC_SHELL_INTEGRATION=1
+
+    __galactic_urlencode() {
+        local LC_ALL=C string="$1" index char
+        for ((index = 0; index < ${#string}; index++)); do
+            char="${string:index:1}"
+            case "$char" in
+            [a-zA-Z0-9/._~-]) printf '%s' "$char" ;;
+            *) printf '%%%02X' "'$char" ;;
+            esac
+        done
+    }
+
+    __galactic_precmd() {
+        local status=$?
+        if [[ -n "$__galactic_prompt_shown" ]]; then
+            printf '\e]133;D;%s\a' "$status"
+        fi
+        __galactic_prompt_shown=1
+        printf '\e]7;file://%s%s\a' "$HOSTNAME" "$(__galactic_urlencode "$PWD")"
+        printf '\e]133;A\a'
+        if [[ "$PS1" != *'133;B'* ]]; then
+            PS1+='\[\e]133;B\a\]'
+        fi
+        return $status
+    }
+
+    PROMPT_COMMAND="__galactic_precmd${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+    PS0+='\[\e]133;C\a\]'
+fi
@@ -0,0 +1,30 @@
+# Galactic shell integration for fish.
+#
+# Add the following line to the end of `~/.config/fish/config.fish`:
+#
+#     galactic shell-integration fish | source
+
+if status is-interactive; and not set -q GALACTIC_SHELL_INTEGRATION
+    set -g GALACTIC_SHELL_INTEGRATION 1
+
+    function __galactic_precmd --on-event fish_prompt
+        set -l last_status $status
+        if set -q __galactic_command_running
+            printf '\e]133;D;%s\a' $last_status
+            set -e __galactic_command_running
+        end
+        printf '\e]7;file://%s%s\a' (hostname) (string escape --style=url -- $PWD)
+        printf '\e]133;A\a'
+    end
+
+    function __galactic_preexec --on-event fish_preexec
+        set -g __galactic_command_running 1
+        printf '\e]133;C\a'
+    end
+
+    functions -c fish_prompt __galactic_fish_prompt
+    function fish_prompt
+        __galactic_fish_prompt
+        printf '\e]133;B\a'
+    end
+end
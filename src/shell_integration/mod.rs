@@ -0,0 +1,18 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn script(&self) -> &'static str {
+        match self {
+            Shell::Bash => include_str!("galactic.bash"),
+            Shell::Zsh => include_str!("galactic.zsh"),
+            Shell::Fish => include_str!("galactic.fish"),
+        }
+    }
+}
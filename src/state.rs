@@ -0,0 +1,99 @@
+//! State that is kept between runs, like window geometry and the windows that
+//! were open when the application quit.
+
+use crate::constants;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// How many recent directories are kept.
+const MAX_RECENT_DIRECTORIES: usize = 20;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    /// The geometry of the last closed window.
+    #[serde(default)]
+    pub window: Option<WindowState>,
+
+    /// The windows that were open when the application quit.
+    #[serde(default)]
+    pub session: Vec<WindowState>,
+
+    /// The working directories of shells, most recent first.
+    #[serde(default)]
+    pub recent_directories: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowState {
+    pub width: i32,
+    pub height: i32,
+
+    #[serde(default)]
+    pub maximized: bool,
+
+    #[serde(default)]
+    pub fullscreen: bool,
+
+    #[serde(default)]
+    pub working_directory: Option<PathBuf>,
+}
+
+impl State {
+    /// Load the state, falling back to an empty state if there is none yet.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(error) => {
+                tracing::warn!("Failed to read state from {path:?}: {error}.");
+                return Self::default();
+            }
+        };
+        toml::from_str(&contents).unwrap_or_else(|error| {
+            tracing::warn!("Failed to parse state from {path:?}: {error}.");
+            Self::default()
+        })
+    }
+
+    /// Move `directory` to the front of the recent directories.
+    pub fn remember_directory(&mut self, directory: &Path) {
+        self.recent_directories.retain(|other| other != directory);
+        self.recent_directories.insert(0, directory.to_path_buf());
+        self.recent_directories.truncate(MAX_RECENT_DIRECTORIES);
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        tracing::debug!("Save state to {path:?}.");
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let contents = toml::to_string(self)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        write_atomically(&path, &contents)
+    }
+
+    fn path() -> Option<PathBuf> {
+        let directory = dirs::state_dir().or_else(dirs::data_dir)?;
+        Some(
+            directory
+                .join(constants::APPLICATION_NAME.to_lowercase())
+                .join("state.toml"),
+        )
+    }
+}
+
+/// Write through a temporary file, so a crash never leaves a partial state.
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let temporary = path.with_extension("toml.tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+}
@@ -19,6 +19,13 @@ where
     Ok(color)
 }
 
+pub fn optional_color<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    color(deserializer).map(Some)
+}
+
 pub fn color_palette<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -2,7 +2,7 @@ use self::error::Result;
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, path::Path};
 
-mod deserialize;
+pub mod deserialize;
 mod error;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
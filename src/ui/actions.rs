@@ -0,0 +1,207 @@
+//! Registry of the actions that are bound to keyboard shortcuts and shown in
+//! menus.
+
+use crate::config::shortcuts::Shortcuts;
+use gtk::{gio, prelude::*};
+
+#[derive(Debug)]
+pub struct Action {
+    pub name: &'static str,
+
+    /// The label in menus.
+    pub title: &'static str,
+    pub accels: &'static [&'static str],
+}
+
+pub static ACTIONS: &[Action] = &[
+    Action {
+        name: "app.new-window",
+        title: "New Window",
+        accels: &["<Ctrl><Shift>n"],
+    },
+    Action {
+        name: "window.toggle-fullscreen",
+        title: "Fullscreen",
+        accels: &["F11"],
+    },
+    Action {
+        name: "window.toggle-header-bar",
+        title: "Show Header Bar",
+        accels: &["<Ctrl><Shift>h"],
+    },
+    Action {
+        name: "window.command-palette",
+        title: "Command Palette",
+        accels: &["<Ctrl><Shift>p"],
+    },
+    Action {
+        name: "terminal.copy",
+        title: "Copy",
+        accels: &["<Ctrl><Shift>c"],
+    },
+    Action {
+        name: "terminal.paste",
+        title: "Paste",
+        accels: &["<Ctrl><Shift>v"],
+    },
+    Action {
+        name: "terminal.select-all",
+        title: "Select All",
+        accels: &["<Ctrl><Shift>a"],
+    },
+    Action {
+        name: "terminal.open-link",
+        title: "Open Link",
+        accels: &[],
+    },
+    Action {
+        name: "terminal.copy-link",
+        title: "Copy Link",
+        accels: &[],
+    },
+    Action {
+        name: "terminal.search",
+        title: "Search…",
+        accels: &["<Ctrl><Shift>f"],
+    },
+    Action {
+        name: "terminal.zoom-in",
+        title: "Zoom In",
+        accels: &["<Ctrl>plus", "<Ctrl>equal"],
+    },
+    Action {
+        name: "terminal.zoom-out",
+        title: "Zoom Out",
+        accels: &["<Ctrl>minus", "<Ctrl>underscore"],
+    },
+    Action {
+        name: "terminal.zoom-reset",
+        title: "Reset Zoom",
+        accels: &["<Ctrl>0"],
+    },
+    Action {
+        name: "terminal.previous-prompt",
+        title: "Previous Prompt",
+        accels: &["<Ctrl><Shift>Up"],
+    },
+    Action {
+        name: "terminal.next-prompt",
+        title: "Next Prompt",
+        accels: &["<Ctrl><Shift>Down"],
+    },
+    Action {
+        name: "terminal.select-output",
+        title: "Select Last Output",
+        accels: &["<Ctrl><Shift>o"],
+    },
+    Action {
+        name: "terminal.copy-output",
+        title: "Copy Last Output",
+        accels: &[],
+    },
+    Action {
+        name: "terminal.copy-html",
+        title: "Copy as HTML",
+        accels: &["<Ctrl><Alt>c"],
+    },
+    Action {
+        name: "terminal.copy-mode",
+        title: "Copy Mode",
+        accels: &["<Ctrl><Shift>space"],
+    },
+    Action {
+        name: "terminal.hints",
+        title: "Hints",
+        accels: &["<Ctrl><Shift>j"],
+    },
+    Action {
+        name: "terminal.clear-scrollback",
+        title: "Clear Scrollback",
+        accels: &["<Ctrl><Shift>k"],
+    },
+    Action {
+        name: "terminal.reset",
+        title: "Reset",
+        accels: &["<Ctrl><Shift>r"],
+    },
+    Action {
+        name: "terminal.reset-and-clear",
+        title: "Reset and Clear",
+        accels: &["<Ctrl><Shift>l"],
+    },
+    Action {
+        name: "terminal.export",
+        title: "Export Scrollback…",
+        accels: &["<Ctrl><Shift>s"],
+    },
+    Action {
+        name: "terminal.toggle-recording",
+        title: "Record",
+        accels: &["<Ctrl><Shift>e"],
+    },
+];
+
+/// Create a controller that activates the registered actions through their
+/// shortcuts.
+pub fn shortcut_controller() -> gtk::ShortcutController {
+    let controller = gtk::ShortcutController::new();
+    controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    set_shortcuts(&controller, &Shortcuts::default());
+    controller
+}
+
+/// Replace the shortcuts of `controller` with the default ones, overridden
+/// by the configured ones.
+pub fn set_shortcuts(controller: &gtk::ShortcutController, shortcuts: &Shortcuts) {
+    while let Some(shortcut) = controller.item(0).and_downcast::<gtk::Shortcut>() {
+        controller.remove_shortcut(&shortcut);
+    }
+
+    for name in shortcuts.0.keys() {
+        if !ACTIONS.iter().any(|action| action.name == name) {
+            tracing::warn!("Ignore shortcuts of unknown action `{name}`.");
+        }
+    }
+
+    for action in ACTIONS {
+        for accel in accels(action, shortcuts) {
+            let Some(trigger) = gtk::ShortcutTrigger::parse_string(accel) else {
+                tracing::error!("Failed to parse shortcut `{accel}` of `{}`.", action.name);
+                continue;
+            };
+            let shortcut =
+                gtk::Shortcut::new(Some(trigger), Some(gtk::NamedAction::new(action.name)));
+            controller.add_shortcut(shortcut);
+        }
+    }
+}
+
+/// The shortcuts of `action`, which are the configured ones if there are any.
+pub fn accels<'a>(action: &'a Action, shortcuts: &'a Shortcuts) -> Vec<&'a str> {
+    match shortcuts.0.get(action.name) {
+        Some(accels) => accels.iter().map(String::as_str).collect(),
+        None => action.accels.to_vec(),
+    }
+}
+
+/// Build a menu of the registered actions named in `sections`, showing the
+/// first shortcut of each.
+pub fn menu(sections: &[&[&str]], shortcuts: &Shortcuts) -> gio::Menu {
+    let menu = gio::Menu::new();
+    for names in sections {
+        let section = gio::Menu::new();
+        for name in *names {
+            let Some(action) = ACTIONS.iter().find(|action| action.name == *name) else {
+                tracing::error!("Missing action `{name}` for menu.");
+                continue;
+            };
+            let item = gio::MenuItem::new(Some(action.title), Some(action.name));
+            if let Some(accel) = accels(action, shortcuts).first() {
+                item.set_attribute_value("accel", Some(&accel.to_variant()));
+            }
+            section.append_item(&item);
+        }
+        menu.append_section(None, &section);
+    }
+    menu
+}
@@ -1,6 +1,22 @@
-use crate::{config::Config, constants, options::Options, theme::Theme, ui::window::Window};
+use crate::{
+    config::Config,
+    constants,
+    control::{self, Request, Response, Server, TerminalInfo},
+    options::{Command, Options},
+    recording::Cast,
+    state::State,
+    theme::Theme,
+    ui::{player::Player, window::Window},
+};
 use adw::{prelude::*, subclass::prelude::*};
 use gtk::{gio, glib};
+use std::{
+    cell::RefCell,
+    fs, io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 glib::wrapper! {
     pub struct Application(ObjectSubclass<imp::Application>)
@@ -17,13 +33,580 @@ impl Application {
             .property("flags", flags)
             .build()
     }
+
+    /// Open a new window. Without a working directory, the shell starts in
+    /// the working directory of the active window if it is known.
+    pub fn new_window(&self, working_directory: Option<&Path>) -> Window {
+        let window = self.build_window(working_directory, false);
+        window.present();
+        window
+    }
+
+    /// Show or hide the drop-down window, opening it on first use.
+    pub fn toggle_dropdown(&self) {
+        let window = self.imp().dropdown.upgrade().unwrap_or_else(|| {
+            let window = self.build_window(None, true);
+            self.imp().dropdown.set(Some(&window));
+            window
+        });
+        window.toggle_dropdown();
+    }
+
+    fn build_window(&self, working_directory: Option<&Path>, dropdown: bool) -> Window {
+        let working_directory = working_directory.map(Path::to_path_buf).or_else(|| {
+            self.active_window()
+                .and_downcast::<Window>()
+                .and_then(|window| window.current_directory())
+        });
+        tracing::debug!("Open new window in {working_directory:?}.");
+
+        let config = self.imp().config.borrow();
+        let window = Window::new(self);
+        if dropdown {
+            window.set_dropdown();
+        }
+        window.apply_config(&config);
+        window.apply_theme(&self.imp().theme.borrow());
+        if !dropdown {
+            if let Some(state) = self.imp().state.borrow().window.as_ref() {
+                if config.session.remember_size {
+                    window.apply_window_state(state);
+                }
+            }
+            window.apply_start_mode(config.window.start_mode);
+            window.connect_close_request(
+                glib::clone!(@weak self as application => @default-return glib::Propagation::Proceed, move |window| {
+                    application.save_state(Some(window));
+                    glib::Propagation::Proceed
+                }),
+            );
+        }
+        window.spawn(&config.general.command, working_directory.as_deref());
+        window
+    }
+
+    /// Open the windows of the previous session in their last working
+    /// directories. Returns the last window opened, if any.
+    fn restore_session(&self) -> Option<Window> {
+        let session = self.imp().state.borrow().session.clone();
+        tracing::info!("Restore session with {} windows.", session.len());
+        session
+            .iter()
+            .map(|state| {
+                let window = self.build_window(state.working_directory.as_deref(), false);
+                window.apply_window_state(state);
+                window.present();
+                window
+            })
+            .last()
+    }
+
+    /// Remember the geometry of a closing window and, if enabled, the windows
+    /// to restore on the next start. Closing the last window keeps it in the
+    /// session unless its shell has exited. Without a closing window the
+    /// application quits and keeps all of its windows. Only the first
+    /// instance saves its windows, later instances keep the saved ones.
+    fn save_state(&self, closing: Option<&Window>) {
+        let config = self.imp().config.borrow();
+        let mut state = self.imp().state.borrow_mut();
+        if let Some(closing) = closing.filter(|_| config.session.remember_size) {
+            state.window = Some(closing.window_state());
+        }
+        let windows: Vec<Window> = self
+            .windows()
+            .into_iter()
+            .filter_map(|window| window.downcast::<Window>().ok())
+            .filter(|window| !window.is_dropdown())
+            .collect();
+        if !self.imp().primary.get() {
+            state.session = State::load().session;
+        } else if !config.session.restore {
+            state.session.clear();
+        } else if !windows.is_empty() {
+            let last = windows.len() <= 1;
+            state.session = windows
+                .iter()
+                .filter(|window| last || closing != Some(*window))
+                .filter(|window| window.terminal().has_child())
+                .map(Window::window_state)
+                .collect();
+        }
+
+        if let Err(error) = state.save() {
+            tracing::warn!("Failed to save state: {error}.");
+        }
+    }
+
+    /// Open a window that plays back the recording at `path`.
+    pub fn play(&self, path: &Path, speed: f64, idle_time_limit: Option<f64>) {
+        let cast = match Cast::load(path, idle_time_limit) {
+            Ok(cast) => cast,
+            Err(error) => {
+                tracing::error!("Failed to load recording from {path:?}: {error}.");
+                return;
+            }
+        };
+
+        let player = Player::new(self, cast, speed);
+        player.apply_config(&self.imp().config.borrow());
+        player.apply_theme(&self.imp().theme.borrow());
+        player.present();
+        player.play();
+    }
+
+    /// Handle the options of an invocation. In single-instance mode, later
+    /// invocations forward their options to the running instance, so paths
+    /// are resolved against the working directory of the invocation.
+    fn handle_options(&self, options: Options, working_directory: Option<&Path>) {
+        let resolve = |path: PathBuf| match working_directory {
+            Some(directory) => directory.join(path),
+            None => path,
+        };
+
+        // The config is shared by all windows, so only the first invocation
+        // decides where it is loaded from.
+        let first = !self.imp().configured.replace(true);
+        if !first {
+            if options.default_config || options.config_path.is_some() {
+                tracing::warn!("Ignore config options of running instance.");
+            }
+        } else {
+            if !options.default_config {
+                let config_path = options.config_path.map(resolve).unwrap_or(
+                    dirs::config_dir()
+                        .unwrap()
+                        .join(constants::APPLICATION_NAME.to_lowercase()),
+                );
+                self.imp().config_path.replace(Some(config_path));
+            }
+            self.load_config();
+            self.watch_config();
+            self.imp().state.replace(State::load());
+            self.imp().primary.set(!control::other_instance_running());
+        }
+
+        match options.command {
+            Some(Command::Play {
+                file,
+                speed,
+                idle_time_limit,
+            }) => self.play(&resolve(file), speed, idle_time_limit),
+            _ if options.dropdown => self.toggle_dropdown(),
+            _ => {
+                // Only the first instance restores the session, later ones
+                // open a single window like in single-instance mode.
+                let restore = first
+                    && self.imp().primary.get()
+                    && self.imp().config.borrow().session.restore
+                    && !self.imp().state.borrow().session.is_empty();
+                let window = restore
+                    .then(|| self.restore_session())
+                    .flatten()
+                    .unwrap_or_else(|| self.new_window(working_directory));
+                if let Some(path) = options.record {
+                    window.start_recording(&resolve(path));
+                }
+            }
+        }
+    }
+
+    /// Listen for requests of `galactic msg`.
+    fn setup_control(&self) {
+        let server = match Server::bind() {
+            Ok(server) => server,
+            Err(error) => {
+                tracing::error!("Failed to listen for control requests: {error}.");
+                return;
+            }
+        };
+
+        glib::unix_fd_add_local(
+            server.as_raw_fd(),
+            glib::IOCondition::IN,
+            glib::clone!(@weak self as application => @default-return glib::ControlFlow::Break, move |_, _| {
+                application.accept_control();
+                glib::ControlFlow::Continue
+            }),
+        );
+        self.imp().control.replace(Some(server));
+    }
+
+    /// Accept a control connection and read its request as it arrives. The
+    /// connection is dropped if the request takes too long.
+    fn accept_control(&self) {
+        let accepted = match self.imp().control.borrow().as_ref() {
+            Some(server) => server.accept(),
+            None => return,
+        };
+        let connection = match accepted {
+            Ok(connection) => connection,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => return,
+            Err(error) => {
+                tracing::warn!("Failed to accept control connection: {error}.");
+                return;
+            }
+        };
+
+        let fd = connection.as_raw_fd();
+        let connection = Rc::new(RefCell::new(Some(connection)));
+        let timer = glib::timeout_add_local_once(
+            control::TIMEOUT,
+            glib::clone!(@strong connection => move || {
+                // Closing the connection also ends reading from it below.
+                if connection.borrow_mut().take().is_some() {
+                    tracing::warn!("Control request timed out.");
+                }
+            }),
+        );
+        let mut timer = Some(timer);
+        glib::unix_fd_add_local(
+            fd,
+            glib::IOCondition::IN | glib::IOCondition::HUP | glib::IOCondition::ERR,
+            glib::clone!(@weak self as application => @default-return glib::ControlFlow::Break, move |_, _| {
+                let result = match connection.borrow_mut().as_mut() {
+                    Some(connection) => connection.read_request(),
+                    None => return glib::ControlFlow::Break,
+                };
+                let request = match result {
+                    Ok(None) => return glib::ControlFlow::Continue,
+                    Ok(Some(request)) => Some(request),
+                    // Other instances connect to check whether this one runs.
+                    Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                        tracing::debug!("Control connection closed without a request.");
+                        None
+                    }
+                    Err(error) => {
+                        tracing::warn!("Failed to read control request: {error}.");
+                        None
+                    }
+                };
+
+                // Either way the connection is done with.
+                let connection = connection.borrow_mut().take();
+                if let Some(timer) = timer.take() {
+                    timer.remove();
+                }
+                if let (Some(connection), Some(request)) = (connection, request) {
+                    tracing::debug!("Handle control request `{request:?}`.");
+                    let response = application.handle_request(request);
+                    if let Err(error) = connection.respond(&response) {
+                        tracing::warn!("Failed to respond to control request: {error}.");
+                    }
+                }
+                glib::ControlFlow::Break
+            }),
+        );
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        match request {
+            // There are no tabs yet, so new tabs open in new windows.
+            Request::NewWindow { working_directory } | Request::NewTab { working_directory } => {
+                let window = self.new_window(working_directory.as_deref());
+                Response::Created {
+                    terminal: window.terminal().id(),
+                }
+            }
+            Request::SendText { terminal, text } => match self.terminal_window(terminal) {
+                Some(window) => {
+                    window.terminal().write_child(text.as_bytes());
+                    Response::Done
+                }
+                None => terminal_not_found(terminal),
+            },
+            Request::SetTitle { terminal, title } => match self.terminal_window(terminal) {
+                Some(window) => {
+                    window.set_title_override(Some(title).filter(|title| !title.is_empty()));
+                    Response::Done
+                }
+                None => terminal_not_found(terminal),
+            },
+            Request::List => {
+                let active = self.active_window();
+                let terminals = self
+                    .windows()
+                    .into_iter()
+                    .filter_map(|window| window.downcast::<Window>().ok())
+                    .map(|window| TerminalInfo {
+                        id: window.terminal().id(),
+                        title: window
+                            .title()
+                            .map(|title| title.to_string())
+                            .unwrap_or_default(),
+                        directory: window.current_directory(),
+                        active: active.as_ref() == Some(window.upcast_ref::<gtk::Window>()),
+                    })
+                    .collect();
+                Response::Terminals { terminals }
+            }
+            Request::Focus { terminal } => match self.terminal_window(Some(terminal)) {
+                Some(window) => {
+                    window.present();
+                    Response::Done
+                }
+                None => terminal_not_found(Some(terminal)),
+            },
+        }
+    }
+
+    /// The window of the terminal with the ID, or the active window.
+    fn terminal_window(&self, terminal: Option<u32>) -> Option<Window> {
+        let mut windows = self
+            .windows()
+            .into_iter()
+            .filter_map(|window| window.downcast::<Window>().ok());
+        match terminal {
+            Some(id) => windows.find(|window| window.terminal().id() == id),
+            None => self.active_window().and_downcast::<Window>(),
+        }
+    }
+
+    fn setup_actions(&self) {
+        let new_window_action = gio::ActionEntry::builder("new-window")
+            .activate(|application: &Self, _, _| {
+                application.new_window(None);
+            })
+            .build();
+        let focus_window_action = gio::ActionEntry::builder("focus-window")
+            .parameter_type(Some(glib::VariantTy::UINT32))
+            .activate(|application: &Self, _, parameter| {
+                let window = parameter
+                    .and_then(|parameter| parameter.get::<u32>())
+                    .and_then(|id| application.window_by_id(id));
+                if let Some(window) = window {
+                    window.present();
+                }
+            })
+            .build();
+        let toggle_dropdown_action = gio::ActionEntry::builder("toggle-dropdown")
+            .activate(|application: &Self, _, _| application.toggle_dropdown())
+            .build();
+        self.add_action_entries([
+            new_window_action,
+            focus_window_action,
+            toggle_dropdown_action,
+        ]);
+    }
+
+    fn config_file(&self) -> Option<PathBuf> {
+        let config_path = self.imp().config_path.borrow();
+        config_path.as_ref().map(|path| path.join("config.toml"))
+    }
+
+    /// The file of the theme picked in the command palette, falling back to
+    /// the configured one.
+    fn theme_file(&self) -> Option<PathBuf> {
+        let theme = self
+            .imp()
+            .theme_override
+            .borrow()
+            .clone()
+            .or_else(|| self.imp().config.borrow().general.theme.clone())?;
+        Some(self.themes_directory()?.join(format!("{theme}.toml")))
+    }
+
+    fn themes_directory(&self) -> Option<PathBuf> {
+        let config_path = self.imp().config_path.borrow();
+        config_path.as_ref().map(|path| path.join("themes"))
+    }
+
+    /// The names of the themes in the themes directory, sorted.
+    pub fn theme_names(&self) -> Vec<String> {
+        let Some(directory) = self.themes_directory() else {
+            return Vec::new();
+        };
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(error) => {
+                tracing::debug!("Failed to list themes in {directory:?}: {error}.");
+                return Vec::new();
+            }
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "toml")
+            })
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn config(&self) -> Config {
+        self.imp().config.borrow().clone()
+    }
+
+    pub fn theme(&self) -> Theme {
+        self.imp().theme.borrow().clone()
+    }
+
+    /// Load the theme with `name` from the themes directory.
+    pub fn load_theme(&self, name: &str) -> Option<Theme> {
+        let path = self.themes_directory()?.join(format!("{name}.toml"));
+        Theme::load(&path)
+            .map_err(|error| tracing::error!("Failed to get theme from path {path:?}: {error}."))
+            .ok()
+    }
+
+    /// Use the theme with `name` in all windows until the application quits.
+    pub fn set_theme(&self, name: &str) {
+        let Some(theme) = self.load_theme(name) else {
+            return;
+        };
+        tracing::info!("Use theme `{name}`.");
+        self.imp().theme_override.replace(Some(name.to_string()));
+        self.imp().theme.replace(theme);
+        for window in self.windows() {
+            if let Some(window) = window.downcast_ref::<Window>() {
+                window.apply_theme(&self.imp().theme.borrow());
+            } else if let Some(player) = window.downcast_ref::<Player>() {
+                player.apply_theme(&self.imp().theme.borrow());
+            }
+        }
+        self.watch_config();
+    }
+
+    pub fn recent_directories(&self) -> Vec<PathBuf> {
+        self.imp().state.borrow().recent_directories.clone()
+    }
+
+    /// Remember a working directory of a shell for the command palette. It is
+    /// saved with the rest of the state.
+    pub fn remember_directory(&self, directory: &Path) {
+        self.imp().state.borrow_mut().remember_directory(directory);
+    }
+
+    fn load_config(&self) {
+        let config = match self.config_file() {
+            Some(config_file) => Config::load(&config_file).unwrap_or_else(|error| {
+                tracing::error!("Failed to get config from path {config_file:?}: {error}.");
+                tracing::info!("Use default config.");
+                Config::default()
+            }),
+            None => {
+                tracing::info!("Use default config.");
+                Config::default()
+            }
+        };
+        self.imp().config.replace(config);
+
+        let theme = match self.theme_file() {
+            Some(theme_file) => Theme::load(&theme_file).unwrap_or_else(|error| {
+                tracing::error!("Failed to get theme from path {theme_file:?}: {error}.");
+                tracing::info!("Use default theme.");
+                Theme::default()
+            }),
+            None => {
+                tracing::info!("Use default theme.");
+                Theme::default()
+            }
+        };
+        self.imp().theme.replace(theme);
+    }
+
+    /// Load the config and theme again and apply them to all windows. The
+    /// current config and theme are kept if loading fails. A change of the
+    /// config file drops the theme picked in the command palette, so the
+    /// configured theme applies again.
+    fn reload_config(&self, config_changed: bool) {
+        let Some(config_file) = self.config_file() else {
+            return;
+        };
+        let config = match Config::load(&config_file) {
+            Ok(config) => config,
+            Err(error) => {
+                tracing::error!("Failed to reload config from path {config_file:?}: {error}.");
+                return;
+            }
+        };
+        self.imp().config.replace(config);
+        if config_changed && self.imp().theme_override.take().is_some() {
+            tracing::info!("Drop theme picked in command palette.");
+        }
+
+        let theme = match self.theme_file().map(|theme_file| Theme::load(&theme_file)) {
+            Some(Ok(theme)) => theme,
+            Some(Err(error)) => {
+                tracing::error!("Failed to reload theme: {error}.");
+                self.imp().theme.borrow().clone()
+            }
+            None => Theme::default(),
+        };
+        self.imp().theme.replace(theme);
+
+        for window in self.windows() {
+            if let Some(window) = window.downcast_ref::<Window>() {
+                window.apply_config(&self.imp().config.borrow());
+                window.apply_theme(&self.imp().theme.borrow());
+            } else if let Some(player) = window.downcast_ref::<Player>() {
+                player.apply_config(&self.imp().config.borrow());
+                player.apply_theme(&self.imp().theme.borrow());
+            }
+        }
+
+        self.watch_config();
+    }
+
+    /// Watch the config and theme files and reload them on changes.
+    fn watch_config(&self) {
+        let config_file = self.config_file();
+        let files = [config_file.clone(), self.theme_file()];
+        let monitors = files
+            .into_iter()
+            .flatten()
+            .filter_map(|path| {
+                let config_changed = config_file.as_ref() == Some(&path);
+                let file = gio::File::for_path(&path);
+                let monitor = file
+                    .monitor_file(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>)
+                    .map_err(|error| tracing::warn!("Failed to watch {path:?}: {error}."))
+                    .ok()?;
+                monitor.connect_changed(
+                    glib::clone!(@weak self as application => move |_, _, _, event| {
+                        if event == gio::FileMonitorEvent::ChangesDoneHint {
+                            tracing::info!("Config changed. Reload config.");
+                            application.reload_config(config_changed);
+                        }
+                    }),
+                );
+                Some(monitor)
+            })
+            .collect();
+
+        for monitor in self.imp().monitors.replace(monitors) {
+            monitor.cancel();
+        }
+    }
+}
+
+fn terminal_not_found(terminal: Option<u32>) -> Response {
+    let message = match terminal {
+        Some(id) => format!("No terminal with ID {id}"),
+        None => "No active terminal".to_string(),
+    };
+    Response::Error { message }
 }
 
 mod imp {
     use super::*;
+    use std::cell::{Cell, RefCell};
 
     #[derive(Debug, Default)]
-    pub struct Application {}
+    pub struct Application {
+        pub configured: Cell<bool>,
+        pub primary: Cell<bool>,
+        pub config_path: RefCell<Option<PathBuf>>,
+        pub config: RefCell<Config>,
+        pub theme: RefCell<Theme>,
+        pub theme_override: RefCell<Option<String>>,
+        pub monitors: RefCell<Vec<gio::FileMonitor>>,
+        pub control: RefCell<Option<Server>>,
+        pub state: RefCell<State>,
+        pub dropdown: glib::WeakRef<Window>,
+    }
 
     #[glib::object_subclass]
     impl ObjectSubclass for Application {
@@ -39,45 +622,38 @@ mod imp {
     }
 
     impl ApplicationImpl for Application {
-        fn activate(&self) {
-            let application = self.obj();
-            let window = Window::new(&*application);
-
-            let options = Options::parse();
-            let config_path = options.config_path.unwrap_or(
-                dirs::config_dir()
-                    .unwrap()
-                    .join(constants::APPLICATION_NAME.to_lowercase()),
-            );
+        fn startup(&self) {
+            self.parent_startup();
+            self.obj().setup_actions();
+            self.obj().setup_control();
+        }
 
-            let config = if options.default_config {
-                tracing::info!("Use default config.");
-                Config::default()
-            } else {
-                let config_path = config_path.join("config.toml");
-                Config::load(&config_path).unwrap_or_else(|error| {
-                    tracing::error!("Failed to get config from path {config_path:?}: {error}.");
-                    tracing::info!("Use default config.");
-                    Config::default()
-                })
-            };
+        fn shutdown(&self) {
+            // Remember the windows that are still open.
+            self.obj().save_state(None);
 
-            let theme = if let Some(theme) = &config.general.theme {
-                let theme_path = config_path.join("themes").join(format!("{theme}.toml"));
-                Theme::load(&theme_path).unwrap_or_else(|error| {
-                    tracing::error!("Failed to get theme from path {theme_path:?}: {error}.");
-                    tracing::info!("Use default theme.");
-                    Theme::default()
-                })
-            } else {
-                tracing::info!("Use default theme.");
-                Theme::default()
-            };
+            // Remove the socket.
+            self.control.take();
+            self.parent_shutdown();
+        }
 
-            window.apply_config(&config);
-            window.apply_theme(&theme);
+        fn activate(&self) {
+            // Activation without a command line, e.g. through D-Bus.
+            self.obj().handle_options(Options::default(), None);
+        }
 
-            window.present();
+        fn command_line(&self, command_line: &gio::ApplicationCommandLine) -> glib::ExitCode {
+            // The arguments were already validated by the invoking process.
+            let options = match Options::try_parse_from(command_line.arguments()) {
+                Ok(options) => options,
+                Err(error) => {
+                    tracing::error!("Failed to parse forwarded options: {error}.");
+                    return glib::ExitCode::FAILURE;
+                }
+            };
+            self.obj()
+                .handle_options(options, command_line.cwd().as_deref());
+            glib::ExitCode::SUCCESS
         }
     }
 
@@ -1,3 +1,6 @@
+pub mod actions;
 pub mod application;
+pub mod palette;
+pub mod player;
 pub mod terminal;
 pub mod window;
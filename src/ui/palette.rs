@@ -0,0 +1,307 @@
+use crate::{
+    fuzzy,
+    ui::{
+        actions::{self, Action, ACTIONS},
+        application::Application,
+        window::Window,
+    },
+};
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::{gdk, glib};
+use std::path::PathBuf;
+
+/// Actions that only make sense where they are offered elsewhere.
+const HIDDEN_ACTIONS: &[&str] = &[
+    "window.command-palette",
+    "terminal.open-link",
+    "terminal.copy-link",
+];
+
+glib::wrapper! {
+    pub struct Palette(ObjectSubclass<imp::Palette>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+#[derive(Debug, Clone)]
+pub enum Entry {
+    Action(&'static Action),
+    Theme(String),
+    Directory(PathBuf),
+}
+
+impl Entry {
+    fn title(&self) -> String {
+        match self {
+            Self::Action(action) => action.title.to_string(),
+            Self::Theme(name) => name.clone(),
+            Self::Directory(path) => {
+                let home = dirs::home_dir();
+                match home.as_deref().map(|home| path.strip_prefix(home)) {
+                    Some(Ok(relative)) => format!("~/{}", relative.display()),
+                    _ => path.display().to_string(),
+                }
+            }
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Action(_) => "Action",
+            Self::Theme(_) => "Theme",
+            Self::Directory(_) => "Open Window in Directory",
+        }
+    }
+}
+
+impl Palette {
+    /// Create a palette with the actions, the themes and the recent
+    /// directories, which runs the picked entry in `window`.
+    pub fn new(window: &Window) -> Self {
+        let palette: Self = glib::Object::new();
+        let imp = palette.imp();
+        imp.window.set(Some(window));
+
+        let mut entries: Vec<Entry> = ACTIONS
+            .iter()
+            .filter(|action| !HIDDEN_ACTIONS.contains(&action.name))
+            .map(Entry::Action)
+            .collect();
+        if let Some(application) = palette.application() {
+            imp.shortcuts.replace(application.config().shortcuts);
+            entries.extend(application.theme_names().into_iter().map(Entry::Theme));
+            entries.extend(
+                application
+                    .recent_directories()
+                    .into_iter()
+                    .map(Entry::Directory),
+            );
+        }
+        imp.entries.replace(entries);
+        palette.update_list();
+        palette
+    }
+
+    fn application(&self) -> Option<Application> {
+        self.imp()
+            .window
+            .upgrade()?
+            .application()
+            .and_downcast::<Application>()
+    }
+
+    /// Show the entries matching the query, best matches first.
+    fn update_list(&self) {
+        let imp = self.imp();
+        let query = imp.search_entry.text();
+        let entries = imp.entries.borrow();
+        let mut shown: Vec<(i64, usize)> = entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| Some((fuzzy::score(&query, &entry.title())?, index)))
+            .collect();
+        shown.sort_by_key(|(score, _)| -score);
+
+        imp.list_box.remove_all();
+        let shortcuts = imp.shortcuts.borrow();
+        for (_, index) in &shown {
+            let entry = &entries[*index];
+            let row = adw::ActionRow::builder()
+                .title(entry.title())
+                .subtitle(entry.kind())
+                .use_markup(false)
+                .activatable(true)
+                .build();
+            if let Entry::Action(action) = entry {
+                if let Some(accel) = actions::accels(action, &shortcuts).first() {
+                    let label = gtk::ShortcutLabel::new(accel);
+                    label.set_valign(gtk::Align::Center);
+                    row.add_suffix(&label);
+                }
+            }
+            imp.list_box.append(&row);
+        }
+        imp.shown
+            .replace(shown.into_iter().map(|(_, index)| index).collect());
+        imp.list_box
+            .select_row(imp.list_box.row_at_index(0).as_ref());
+    }
+
+    /// Move the selection by `step` rows and keep it in view.
+    fn move_selection(&self, step: i32) {
+        let list_box = self.imp().list_box.get();
+        let index = list_box
+            .selected_row()
+            .map_or(0, |row| row.index() + step)
+            .max(0);
+        if let Some(row) = list_box.row_at_index(index) {
+            list_box.select_row(Some(&row));
+            // Focusing the row scrolls it into view.
+            row.grab_focus();
+            self.imp().search_entry.grab_focus_without_selecting();
+        }
+    }
+
+    fn entry(&self, row: &gtk::ListBoxRow) -> Option<Entry> {
+        let index = *self.imp().shown.borrow().get(row.index() as usize)?;
+        self.imp().entries.borrow().get(index).cloned()
+    }
+
+    /// Preview the theme of the selected entry in the window, or go back to
+    /// the theme in use.
+    fn preview(&self, row: Option<&gtk::ListBoxRow>) {
+        let imp = self.imp();
+        let (Some(window), Some(application)) = (imp.window.upgrade(), self.application()) else {
+            return;
+        };
+        match row.and_then(|row| self.entry(row)) {
+            Some(Entry::Theme(name)) => {
+                if let Some(theme) = application.load_theme(&name) {
+                    window.apply_theme(&theme);
+                    imp.previewing.set(true);
+                }
+            }
+            _ if imp.previewing.replace(false) => window.apply_theme(&application.theme()),
+            _ => {}
+        }
+    }
+
+    fn activate(&self, row: &gtk::ListBoxRow) {
+        let Some(entry) = self.entry(row) else {
+            return;
+        };
+        let (Some(window), Some(application)) = (self.imp().window.upgrade(), self.application())
+        else {
+            return;
+        };
+        self.close();
+
+        tracing::debug!("Run `{entry:?}` from command palette.");
+        match entry {
+            Entry::Action(action) => {
+                if let Err(error) = window.terminal().activate_action(action.name, None) {
+                    tracing::warn!("Failed to activate action `{}`: {error}.", action.name);
+                }
+            }
+            Entry::Theme(name) => application.set_theme(&name),
+            Entry::Directory(path) => {
+                application.new_window(Some(&path));
+            }
+        }
+    }
+}
+
+mod imp {
+    use super::*;
+    use crate::config::shortcuts::Shortcuts;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(string = "
+    using Gtk 4.0;
+    using Adw 1;
+    template $GalacticPalette : Adw.Dialog {
+        title: 'Command Palette';
+        content-width: 500;
+        content-height: 420;
+        focus-widget: search_entry;
+        Adw.ToolbarView {
+            [top]
+            Adw.HeaderBar {
+                title-widget: SearchEntry search_entry {
+                    hexpand: true;
+                    placeholder-text: 'Search actions, themes and directories';
+                };
+            }
+            content: ScrolledWindow {
+                hscrollbar-policy: never;
+                ListBox list_box {
+                    selection-mode: browse;
+                    styles ['navigation-sidebar']
+                }
+            };
+        }
+    }
+    ")]
+    pub struct Palette {
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+
+        #[template_child]
+        pub list_box: TemplateChild<gtk::ListBox>,
+
+        pub window: glib::WeakRef<Window>,
+        pub shortcuts: RefCell<Shortcuts>,
+        pub entries: RefCell<Vec<Entry>>,
+
+        /// The indices of the entries shown in the list, in order.
+        pub shown: RefCell<Vec<usize>>,
+
+        /// Whether the window shows a theme other than the one in use.
+        pub previewing: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Palette {
+        const NAME: &'static str = "GalacticPalette";
+        type Type = super::Palette;
+        type ParentType = adw::Dialog;
+
+        fn class_init(class: &mut Self::Class) {
+            class.bind_template();
+        }
+
+        fn instance_init(object: &glib::subclass::InitializingObject<Self>) {
+            object.init_template();
+        }
+    }
+
+    impl ObjectImpl for Palette {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+
+            self.search_entry
+                .connect_search_changed(glib::clone!(@weak obj => move |_| {
+                    obj.update_list();
+                }));
+            self.search_entry
+                .connect_activate(glib::clone!(@weak obj => move |_| {
+                    if let Some(row) = obj.imp().list_box.selected_row() {
+                        obj.activate(&row);
+                    }
+                }));
+
+            // Move through the list while typing.
+            let key_controller = gtk::EventControllerKey::new();
+            key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+            key_controller.connect_key_pressed(
+                glib::clone!(@weak obj => @default-return glib::Propagation::Proceed, move |_, key, _, _| {
+                    match key {
+                        gdk::Key::Up => obj.move_selection(-1),
+                        gdk::Key::Down => obj.move_selection(1),
+                        _ => return glib::Propagation::Proceed,
+                    }
+                    glib::Propagation::Stop
+                }),
+            );
+            self.search_entry.add_controller(key_controller);
+
+            self.list_box
+                .connect_row_selected(glib::clone!(@weak obj => move |_, row| {
+                    obj.preview(row);
+                }));
+            self.list_box
+                .connect_row_activated(glib::clone!(@weak obj => move |_, row| {
+                    obj.activate(row);
+                }));
+
+            // Leaving without picking a theme restores the theme in use.
+            obj.connect_closed(|palette| palette.preview(None));
+        }
+    }
+
+    impl WidgetImpl for Palette {}
+
+    impl AdwDialogImpl for Palette {}
+}
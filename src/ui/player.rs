@@ -0,0 +1,352 @@
+use crate::{
+    config::Config,
+    recording::{Cast, EventKind},
+    theme::Theme,
+};
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::{gdk, gio, glib};
+use std::time::{Duration, Instant};
+
+const SEEK_STEP: f64 = 5.0;
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 16.0;
+
+/// The longest timeout glib can schedule.
+const MAX_DELAY: Duration = Duration::from_millis(u32::MAX as u64);
+
+glib::wrapper! {
+    pub struct Player(ObjectSubclass<imp::Player>)
+        @extends adw::ApplicationWindow, gtk::ApplicationWindow, gtk::Window, gtk::Widget,
+        @implements gio::ActionGroup, gio::ActionMap, gtk::Accessible, gtk::Buildable,
+                    gtk::ConstraintTarget, gtk::Native, gtk::Root, gtk::ShortcutManager;
+}
+
+impl Player {
+    pub fn new<P: IsA<gtk::Application>>(application: &P, cast: Cast, speed: f64) -> Self {
+        let player: Self = glib::Object::builder()
+            .property("application", application)
+            .build();
+        player.set_title(Some("Galactic Player"));
+        player.imp().position.set_upper(cast.duration());
+        player.imp().terminal.set_grid_size(cast.width, cast.height);
+        player.imp().cast.replace(cast);
+        player.imp().speed.set(speed.clamp(MIN_SPEED, MAX_SPEED));
+        player
+    }
+
+    pub fn apply_config(&self, config: &Config) {
+        self.set_size_request(600, 400);
+        self.set_default_size(
+            config.window.size.width as i32,
+            config.window.size.height as i32,
+        );
+        self.imp().terminal.apply_config(config);
+    }
+
+    pub fn apply_theme(&self, theme: &Theme) {
+        self.imp().css_provider.load_from_string(&format!(
+            "headerbar {{ background-color: {}; color: {}; box-shadow: none; }}",
+            theme.background, theme.foreground,
+        ));
+        self.imp().terminal.apply_theme(theme);
+    }
+
+    pub fn play(&self) {
+        let imp = self.imp();
+        if imp.anchor.get().is_some() {
+            return;
+        }
+
+        // Start over when the end has been reached.
+        if imp.next.get() >= imp.cast.borrow().events.len() {
+            self.seek(0.0);
+        }
+        imp.anchor.set(Some((Instant::now(), imp.time.get())));
+        self.schedule();
+        self.update_controls();
+    }
+
+    pub fn pause(&self) {
+        let imp = self.imp();
+        imp.time.set(self.current_time());
+        imp.anchor.set(None);
+        if let Some(timer) = imp.timer.take() {
+            timer.remove();
+        }
+        self.update_controls();
+    }
+
+    pub fn toggle_pause(&self) {
+        if self.imp().anchor.get().is_some() {
+            self.pause();
+        } else {
+            self.play();
+        }
+    }
+
+    /// Jump to `time` seconds into the recording. Jumping backwards replays
+    /// the recording from the start, because output cannot be undone.
+    pub fn seek(&self, time: f64) {
+        let imp = self.imp();
+        let time = time.clamp(0.0, imp.cast.borrow().duration());
+        tracing::debug!("Seek to {time:.1} seconds.");
+
+        if time < self.current_time() {
+            let cast = imp.cast.borrow();
+            imp.terminal.reset_and_clear();
+            imp.terminal.set_grid_size(cast.width, cast.height);
+            imp.next.set(0);
+        }
+
+        imp.time.set(time);
+        if imp.anchor.get().is_some() {
+            imp.anchor.set(Some((Instant::now(), time)));
+        }
+        self.advance();
+    }
+
+    pub fn set_speed(&self, speed: f64) {
+        let imp = self.imp();
+        let time = self.current_time();
+        imp.time.set(time);
+        imp.speed.set(speed.clamp(MIN_SPEED, MAX_SPEED));
+        if imp.anchor.get().is_some() {
+            imp.anchor.set(Some((Instant::now(), time)));
+            self.schedule();
+        }
+        self.update_controls();
+    }
+
+    /// The position in the recording in seconds.
+    fn current_time(&self) -> f64 {
+        let imp = self.imp();
+        match imp.anchor.get() {
+            Some((instant, time)) => time + instant.elapsed().as_secs_f64() * imp.speed.get(),
+            None => imp.time.get(),
+        }
+    }
+
+    /// Show all events up to the current position and wait for the next one.
+    fn advance(&self) {
+        let imp = self.imp();
+        let time = self.current_time();
+        let cast = imp.cast.borrow();
+        while let Some(event) = cast.events.get(imp.next.get()) {
+            if event.time > time {
+                break;
+            }
+            match &event.kind {
+                EventKind::Output(data) => imp.terminal.feed(data.as_bytes()),
+                EventKind::Resize(width, height) => imp.terminal.set_grid_size(*width, *height),
+            }
+            imp.next.set(imp.next.get() + 1);
+        }
+        drop(cast);
+
+        self.schedule();
+        self.update_controls();
+    }
+
+    fn schedule(&self) {
+        let imp = self.imp();
+        if let Some(timer) = imp.timer.take() {
+            timer.remove();
+        }
+        if imp.anchor.get().is_none() {
+            return;
+        }
+
+        let next = imp
+            .cast
+            .borrow()
+            .events
+            .get(imp.next.get())
+            .map(|event| event.time);
+        let Some(next) = next else {
+            tracing::debug!("Reached the end of the recording.");
+            self.pause();
+            return;
+        };
+
+        let delay = ((next - self.current_time()) / imp.speed.get()).max(0.0);
+        let delay = Duration::try_from_secs_f64(delay)
+            .unwrap_or(Duration::MAX)
+            .min(MAX_DELAY);
+        let timer = glib::timeout_add_local_once(
+            delay,
+            glib::clone!(@weak self as player => move || {
+                player.imp().timer.take();
+                player.advance();
+            }),
+        );
+        imp.timer.replace(Some(timer));
+    }
+
+    fn update_controls(&self) {
+        let imp = self.imp();
+        let time = self.current_time();
+        imp.position.set_value(time);
+
+        let playing = imp.anchor.get().is_some();
+        imp.pause_button.set_icon_name(if playing {
+            "media-playback-pause-symbolic"
+        } else {
+            "media-playback-start-symbolic"
+        });
+        imp.status.set_label(&format!(
+            "{} / {} · {}×",
+            format_time(time),
+            format_time(imp.cast.borrow().duration()),
+            imp.speed.get()
+        ));
+    }
+}
+
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+mod imp {
+    use crate::ui::terminal::Terminal;
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(string = "
+    using Gtk 4.0;
+    using Adw 1;
+    using Vte 3.91;
+    template $GalacticPlayer : Adw.ApplicationWindow {
+        Box {
+            orientation: vertical;
+            Adw.HeaderBar header_bar {
+                title-widget: Scale scale {
+                    hexpand: true;
+                    width-request: 300;
+                    draw-value: false;
+                    adjustment: Adjustment position {
+                        lower: 0;
+                        step-increment: 1;
+                        page-increment: 5;
+                    };
+                };
+                [start]
+                Button pause_button {
+                    icon-name: 'media-playback-start-symbolic';
+                    tooltip-text: 'Play or Pause';
+                    action-name: 'player.toggle-pause';
+                }
+                [end]
+                Label status {}
+            }
+            $GalacticTerminal terminal {
+                vexpand: true;
+            }
+        }
+    }
+    ")]
+    pub struct Player {
+        #[template_child]
+        pub header_bar: TemplateChild<adw::HeaderBar>,
+
+        #[template_child]
+        pub pause_button: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub scale: TemplateChild<gtk::Scale>,
+
+        #[template_child]
+        pub position: TemplateChild<gtk::Adjustment>,
+
+        #[template_child]
+        pub status: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub terminal: TemplateChild<Terminal>,
+
+        pub css_provider: gtk::CssProvider,
+        pub cast: RefCell<Cast>,
+        pub next: Cell<usize>,
+        pub time: Cell<f64>,
+        pub anchor: Cell<Option<(Instant, f64)>>,
+        pub speed: Cell<f64>,
+        pub timer: RefCell<Option<glib::SourceId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Player {
+        const NAME: &'static str = "GalacticPlayer";
+        type Type = super::Player;
+        type ParentType = adw::ApplicationWindow;
+
+        fn class_init(class: &mut Self::Class) {
+            class.bind_template();
+
+            class.install_action("player.toggle-pause", None, |player, _, _| {
+                player.toggle_pause();
+            });
+            class.install_action("player.seek-backward", None, |player, _, _| {
+                player.seek(player.current_time() - SEEK_STEP);
+            });
+            class.install_action("player.seek-forward", None, |player, _, _| {
+                player.seek(player.current_time() + SEEK_STEP);
+            });
+            class.install_action("player.faster", None, |player, _, _| {
+                player.set_speed(player.imp().speed.get() * 2.0);
+            });
+            class.install_action("player.slower", None, |player, _, _| {
+                player.set_speed(player.imp().speed.get() / 2.0);
+            });
+
+            let none = gdk::ModifierType::empty();
+            class.add_binding_action(gdk::Key::space, none, "player.toggle-pause");
+            class.add_binding_action(gdk::Key::Left, none, "player.seek-backward");
+            class.add_binding_action(gdk::Key::Right, none, "player.seek-forward");
+            class.add_binding_action(gdk::Key::plus, none, "player.faster");
+            class.add_binding_action(gdk::Key::minus, none, "player.slower");
+        }
+
+        fn instance_init(object: &glib::subclass::InitializingObject<Self>) {
+            object.init_template();
+        }
+    }
+
+    impl ObjectImpl for Player {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+
+            // FIXME This way of applying custom CSS is deprecated and needs a replacement.
+            #[allow(deprecated)]
+            self.header_bar
+                .style_context()
+                .add_provider(&self.css_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+
+            // Seek when the position is changed by the user.
+            self.scale
+                .connect_change_value(glib::clone!(@weak obj => @default-return glib::Propagation::Stop, move |_, _, value| {
+                    obj.seek(value);
+                    glib::Propagation::Stop
+                }));
+
+            self.speed.set(1.0);
+            self.terminal.set_read_only();
+        }
+
+        fn dispose(&self) {
+            if let Some(timer) = self.timer.take() {
+                timer.remove();
+            }
+        }
+    }
+
+    impl WidgetImpl for Player {}
+
+    impl WindowImpl for Player {}
+
+    impl ApplicationWindowImpl for Player {}
+
+    impl AdwApplicationWindowImpl for Player {}
+}
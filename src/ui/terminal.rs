@@ -1,16 +1,71 @@
-use crate::{config::Config, theme::Theme};
-use adw::subclass::prelude::*;
-use gtk::prelude::*;
-use gtk::{gdk, gio, glib};
-use std::str::FromStr;
+use crate::{
+    blocks::{Block, Blocks, Position},
+    config::{
+        bell::BellMode,
+        clipboard::Permission,
+        cursor::{CursorBlink, CursorShape},
+        drag_and_drop::DragAndDrop,
+        hints::{HintAction, Hints},
+        scrollback::ScrollbackLines,
+        Config,
+    },
+    control,
+    copy_mode::{self, CopyMode, Input, Outcome, SelectionKind},
+    export::{self, Format},
+    hints::{self, Hint, Pattern},
+    osc::{self, Mark, Scanner, Selection, Sequence},
+    paste, process, quote,
+    recording::Recorder,
+    theme::Theme,
+    ui::actions,
+};
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::{
+    gdk, gio,
+    glib::{self, Unichar},
+};
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 use vte::prelude::*;
 
+/// How long queued sequences wait for VTE to process the output in front of
+/// them at most.
+const PROCESSING_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Duration of the visual bell in milliseconds.
+const VISUAL_BELL_DURATION: u32 = 250;
+
+/// Width of the command markers drawn in the left padding.
+const GUTTER_MARKER_WIDTH: f64 = 3.0;
+
+/// How long a child process has to run before failed restarts are counted
+/// from zero again.
+const STABLE_RUNTIME: Duration = Duration::from_secs(10);
+
+/// The longest time in seconds to wait before a restart, however often the
+/// delay grew.
+const MAX_RESPAWN_DELAY: f64 = 3600.0;
+
 glib::wrapper! {
     pub struct Terminal(ObjectSubclass<imp::Terminal>)
         @extends adw::Bin, gtk::Widget,
         @implements gio::ActionGroup, gio::ActionMap;
 }
 
+/// Output of the child process waiting to be fed to VTE.
+#[derive(Debug)]
+pub enum Output {
+    Data(Vec<u8>),
+    Sequence(Sequence),
+}
+
 impl Default for Terminal {
     fn default() -> Self {
         Self::new()
@@ -22,25 +77,84 @@ impl Terminal {
         glib::Object::builder().build()
     }
 
-    pub fn apply_config(&self, config: &Config) {
+    /// Spawn the child process on a pseudo terminal owned by Galactic. Its
+    /// output is scanned for sequences before it is fed to VTE.
+    pub fn spawn(&self, command: &[String], working_directory: Option<&Path>) {
+        self.imp().command.replace(command.to_vec());
+        let pty = match vte::Pty::new_sync(vte::PtyFlags::DEFAULT, None::<&gio::Cancellable>) {
+            Ok(pty) => pty,
+            Err(error) => {
+                tracing::error!("Failed to create pseudo terminal: {error}.");
+                return;
+            }
+        };
+        self.imp().pty.replace(Some(pty.clone()));
+        self.reset_modes();
+        self.update_size();
+
+        // Read output of the child process.
+        glib::unix_fd_add_local(
+            pty.fd().as_raw_fd(),
+            glib::IOCondition::IN | glib::IOCondition::HUP | glib::IOCondition::ERR,
+            glib::clone!(@weak self as terminal, @weak pty => @default-return glib::ControlFlow::Break, move |_, _| {
+                match terminal.read_child(&pty) {
+                    Ok(0) => glib::ControlFlow::Break,
+                    Ok(_) => glib::ControlFlow::Continue,
+                    Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                        glib::ControlFlow::Continue
+                    }
+                    Err(error) => {
+                        tracing::debug!("Stop reading from child process: {error}.");
+                        glib::ControlFlow::Break
+                    }
+                }
+            }),
+        );
+
+        // Tell the shell how to control the terminal with `galactic msg`.
+        let environment = [
+            format!("{}={}", control::TERMINAL_VARIABLE, self.id()),
+            format!(
+                "{}={}",
+                control::SOCKET_VARIABLE,
+                control::socket_path().display()
+            ),
+        ];
+
         // Spawn terminal child process.
-        let terminal = self.imp().terminal.get();
-        terminal.spawn_async(
-            vte::PtyFlags::DEFAULT,
-            None,
-            &config
-                .general
-                .command
-                .iter()
-                .map(|s| s.as_str())
-                .collect::<Vec<_>>(),
-            &[],
+        pty.spawn_async(
+            working_directory.and_then(|path| path.to_str()),
+            &command.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            &environment.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
             glib::SpawnFlags::DEFAULT,
             || {},
             -1,
             None::<&gio::Cancellable>,
-            |_| {},
+            glib::clone!(@weak self as terminal => move |result| match result {
+                Ok(pid) => {
+                    tracing::info!("Spawned child process {pid:?}.");
+                    terminal.imp().child.set(Some(pid));
+                    terminal.imp().spawned_at.set(Some(Instant::now()));
+                    glib::child_watch_add_local(
+                        pid,
+                        glib::clone!(@weak terminal => move |_, status| {
+                            terminal.child_exited(status);
+                        }),
+                    );
+                }
+                Err(error) => tracing::error!("Failed to spawn child process: {error}."),
+            }),
         );
+    }
+
+    pub fn apply_config(&self, config: &Config) {
+        let terminal = self.imp().terminal.get();
+        self.imp().config.replace(config.clone());
+
+        // Configure shortcuts.
+        if let Some(controller) = self.imp().shortcut_controller.get() {
+            actions::set_shortcuts(controller, &config.shortcuts);
+        }
 
         // Configure font.
         let font_description = gtk::pango::FontDescription::from_string(&config.general.font);
@@ -48,25 +162,67 @@ impl Terminal {
 
         // Configure scroll bar.
         let scrolled_window = self.imp().scrolled_window.get();
-        if !config.window.scroll_bar {
-            scrolled_window.set_vscrollbar_policy(gtk::PolicyType::Never);
-        }
+        scrolled_window.set_vscrollbar_policy(if config.window.scroll_bar {
+            gtk::PolicyType::Automatic
+        } else {
+            gtk::PolicyType::Never
+        });
+
+        // Configure scrollback. VTE keeps the scrollback in compressed and
+        // encrypted temporary files, so large histories do not stay in memory.
+        terminal.set_scrollback_lines(self.scrollback_lines());
+        terminal.set_scroll_on_output(config.scrollback.on_output);
+        terminal.set_scroll_on_keystroke(config.scrollback.on_keystroke);
 
         // Configure padding.
-        // FIXME This way of applying custom CSS is deprecated and needs a replacement.
-        let terminal_css_provider = gtk::CssProvider::new();
-        terminal_css_provider.load_from_string(&format!(
+        self.imp().css_provider.load_from_string(&format!(
             "vte-terminal {{ padding: {}px {}px; }}",
             config.window.padding.vertical, config.window.padding.horizontal
         ));
-        #[allow(deprecated)]
-        terminal.style_context().add_provider(
-            &terminal_css_provider,
-            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
-        );
+
+        // Configure cursor.
+        terminal.set_cursor_shape(match config.cursor.shape {
+            CursorShape::Block => vte::CursorShape::Block,
+            CursorShape::Ibeam => vte::CursorShape::Ibeam,
+            CursorShape::Underline => vte::CursorShape::Underline,
+        });
+        self.restart_blink_timeout();
+        if !config.cursor.application_shape {
+            self.reset_cursor_style();
+        }
+        self.apply_cursor_colors();
+
+        // Configure hints.
+        self.set_hint_patterns(&config.hints);
+    }
+
+    /// Compile the hint patterns and let VTE highlight their matches under
+    /// the pointer, so they can also be opened with Ctrl and a click.
+    fn set_hint_patterns(&self, config: &Hints) {
+        let terminal = self.imp().terminal.get();
+        terminal.match_remove_all();
+
+        let mut patterns = Vec::new();
+        for pattern in &config.patterns {
+            match Pattern::new(&pattern.regex, pattern.action) {
+                Ok(compiled) => patterns.push(compiled),
+                Err(error) => {
+                    tracing::warn!("Ignore invalid hint pattern `{}`: {error}.", pattern.regex)
+                }
+            }
+        }
+        patterns.extend(Pattern::defaults());
+
+        for pattern in &patterns {
+            let tag = terminal.match_add_regex(pattern.regex(), 0);
+            terminal.match_set_cursor_name(tag, "pointer");
+        }
+        self.imp().hint_patterns.replace(patterns);
     }
 
     pub fn apply_theme(&self, theme: &Theme) {
+        self.imp().theme.replace(theme.clone());
+
         // Translate colors to `gtk::gdk::RGBA` colors. Unwrapping is safe
         // because the theme deserializer checks for valid hex values.
         let foreground = gtk::gdk::RGBA::from_str(&theme.foreground).unwrap();
@@ -84,17 +240,1691 @@ impl Terminal {
             Some(&background),
             &palette.iter().collect::<Vec<_>>(),
         );
+        self.apply_cursor_colors();
+        self.imp().gutter.queue_draw();
+    }
+
+    /// Setting the theme colors resets the cursor colors, so they are applied
+    /// after both the config and the theme.
+    fn apply_cursor_colors(&self) {
+        // Unwrapping is safe because the config deserializer checks for valid
+        // hex values.
+        let config = self.imp().config.borrow();
+        let color = config
+            .cursor
+            .color
+            .as_ref()
+            .map(|color| gdk::RGBA::from_str(color).unwrap());
+        let text_color = config
+            .cursor
+            .text_color
+            .as_ref()
+            .map(|color| gdk::RGBA::from_str(color).unwrap());
+
+        let terminal = self.imp().terminal.get();
+        terminal.set_color_cursor(color.as_ref());
+        terminal.set_color_cursor_foreground(text_color.as_ref());
+    }
+
+    /// Reset the cursor style requested by the application to the configured
+    /// one. The sequence is only fed to VTE and never reaches the child.
+    fn reset_cursor_style(&self) {
+        self.imp().terminal.feed(b"\x1b[0 q");
+    }
+
+    /// The ID that identifies the terminal for `galactic msg`. IDs are not
+    /// reused while the application runs.
+    pub fn id(&self) -> u32 {
+        self.imp().id.get()
+    }
+
+    pub fn connect_child_exited<F: Fn(i32) + 'static>(&self, callback: F) {
+        self.connect_local("child-exited", false, move |values| {
+            callback(values[1].get().unwrap_or_default());
+            None
+        });
+    }
+
+    pub fn connect_respawn_countdown<F: Fn(u32) + 'static>(&self, callback: F) {
+        self.connect_local("respawn-countdown", false, move |values| {
+            callback(values[1].get().unwrap_or_default());
+            None
+        });
+    }
+
+    pub fn connect_recording_changed<F: Fn(bool) + 'static>(&self, callback: F) {
+        self.connect_local("recording-changed", false, move |values| {
+            callback(values[1].get().unwrap_or_default());
+            None
+        });
+    }
+
+    pub fn connect_directory_changed<F: Fn() + 'static>(&self, callback: F) {
+        self.imp()
+            .terminal
+            .connect_current_directory_uri_changed(move |_| {
+                callback();
+            });
     }
 
-    pub fn connect_child_exited<F: Fn() + 'static>(&self, callback: F) {
-        self.imp().terminal.connect_child_exited(move |_, _| {
+    pub fn connect_title_changed<F: Fn() + 'static>(&self, callback: F) {
+        self.imp().terminal.connect_window_title_changed(move |_| {
             callback();
         });
     }
+
+    /// Spawn the last command again in the last known working directory
+    /// after the child process exited. Cancels a scheduled restart and
+    /// starts counting restart attempts from zero.
+    pub fn respawn(&self) {
+        self.cancel_respawn();
+        self.imp().respawn_attempts.set(0);
+        self.spawn_again();
+    }
+
+    /// Restart the child process after the delay of the restart policy,
+    /// which grows with every attempt. Returns `false` once the maximum
+    /// number of attempts is reached. The remaining seconds are reported
+    /// through the `respawn-countdown` signal, down to zero on restart.
+    pub fn schedule_respawn(&self) -> bool {
+        let imp = self.imp();
+        let policy = imp.config.borrow().restart.clone();
+        let stable = imp
+            .spawned_at
+            .get()
+            .is_some_and(|spawned_at| spawned_at.elapsed() >= STABLE_RUNTIME);
+        if stable {
+            imp.respawn_attempts.set(0);
+        }
+
+        let attempt = imp.respawn_attempts.get();
+        if policy.max_attempts > 0 && attempt >= policy.max_attempts {
+            tracing::info!("Give up restarting after {attempt} attempts.");
+            return false;
+        }
+        imp.respawn_attempts.set(attempt + 1);
+
+        let delay = policy.delay.max(0.0) * policy.backoff.max(1.0).powi(attempt as i32);
+        let delay = delay.min(MAX_RESPAWN_DELAY);
+        tracing::debug!("Restart child process in {delay:.1} seconds.");
+        let delay = Duration::try_from_secs_f64(delay).unwrap_or_default();
+        imp.respawn_deadline.set(Some(Instant::now() + delay));
+        self.tick_respawn();
+        true
+    }
+
+    pub fn cancel_respawn(&self) {
+        self.imp().respawn_deadline.take();
+        if let Some(timer) = self.imp().respawn_timer.take() {
+            timer.remove();
+        }
+    }
+
+    /// The number of restarts since the child process last ran stably.
+    pub fn respawn_attempts(&self) -> u32 {
+        self.imp().respawn_attempts.get()
+    }
+
+    fn spawn_again(&self) {
+        if self.has_child() {
+            return;
+        }
+        let command = self.imp().command.borrow().clone();
+        tracing::info!("Respawn child process `{command:?}`.");
+        self.spawn(&command, self.current_directory().as_deref());
+    }
+
+    /// Report the remaining seconds of a scheduled restart and wake up again
+    /// when the next second starts, or restart once the delay is over.
+    fn tick_respawn(&self) {
+        let Some(deadline) = self.imp().respawn_deadline.get() else {
+            return;
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            self.imp().respawn_deadline.take();
+            self.emit_by_name::<()>("respawn-countdown", &[&0u32]);
+            self.spawn_again();
+            return;
+        }
+
+        let seconds = remaining.as_secs_f64().ceil() as u32;
+        self.emit_by_name::<()>("respawn-countdown", &[&seconds]);
+        let wait = remaining.saturating_sub(Duration::from_secs(u64::from(seconds) - 1));
+        let timer = glib::timeout_add_local_once(
+            wait,
+            glib::clone!(@weak self as terminal => move || {
+                terminal.imp().respawn_timer.take();
+                terminal.tick_respawn();
+            }),
+        );
+        self.imp().respawn_timer.replace(Some(timer));
+    }
+
+    /// Let the cursor blink as configured and stop it after the configured
+    /// time without input. The desktop setting is left alone, as it applies
+    /// to every widget.
+    fn restart_blink_timeout(&self) {
+        let imp = self.imp();
+        let (blink, timeout) = {
+            let config = imp.config.borrow();
+            (config.cursor.blink, config.cursor.blink_timeout)
+        };
+        if let Some(timer) = imp.blink_timer.take() {
+            timer.remove();
+        }
+        imp.terminal.set_cursor_blink_mode(match blink {
+            CursorBlink::System => vte::CursorBlinkMode::System,
+            CursorBlink::On => vte::CursorBlinkMode::On,
+            CursorBlink::Off => vte::CursorBlinkMode::Off,
+        });
+
+        let Some(timeout) = timeout.filter(|_| blink != CursorBlink::Off) else {
+            return;
+        };
+        let timer = glib::timeout_add_seconds_local_once(
+            timeout,
+            glib::clone!(@weak self as terminal => move || {
+                terminal.imp().blink_timer.take();
+                terminal
+                    .imp()
+                    .terminal
+                    .set_cursor_blink_mode(vte::CursorBlinkMode::Off);
+            }),
+        );
+        imp.blink_timer.replace(Some(timer));
+    }
+
+    /// Whether the child process is still running.
+    pub fn has_child(&self) -> bool {
+        self.imp().child.get().is_some()
+    }
+
+    /// The names of the processes started by the shell that are running in
+    /// the foreground.
+    pub fn foreground_processes(&self) -> Vec<String> {
+        let (Some(pty), Some(child)) = (self.imp().pty.borrow().clone(), self.imp().child.get())
+        else {
+            return Vec::new();
+        };
+        process::foreground_processes(pty.fd().as_raw_fd(), child.0)
+    }
+
+    /// The working directory reported by the shell through OSC 7. Only
+    /// directories on the local host are returned.
+    pub fn current_directory(&self) -> Option<PathBuf> {
+        let uri = self.imp().terminal.current_directory_uri()?;
+        match glib::filename_from_uri(&uri) {
+            Ok((path, hostname)) => match hostname {
+                Some(hostname) if hostname != "localhost" && hostname != glib::host_name() => {
+                    tracing::debug!("Ignore directory `{uri}` on remote host.");
+                    None
+                }
+                _ => Some(path),
+            },
+            Err(error) => {
+                tracing::warn!("Failed to parse directory `{uri}`: {error}.");
+                None
+            }
+        }
+    }
+
+    /// The title set by the running application through OSC 0 or OSC 2.
+    pub fn title(&self) -> Option<String> {
+        self.imp()
+            .terminal
+            .window_title()
+            .map(|title| title.to_string())
+    }
+
+    /// Write input to the child process. Input that cannot be written right
+    /// away is buffered until the pseudo terminal is writable again.
+    pub fn write_child(&self, data: &[u8]) {
+        let Some(pty) = self.imp().pty.borrow().clone() else {
+            return;
+        };
+        if self.imp().config.borrow().recording.input {
+            self.record(|recorder| recorder.input(data));
+        }
+
+        let mut input = self.imp().input.borrow_mut();
+        let waiting = !input.is_empty();
+        input.extend_from_slice(data);
+        drop(input);
+        if waiting {
+            return;
+        }
+
+        if self.flush_input(&pty) == glib::ControlFlow::Continue {
+            glib::unix_fd_add_local(
+                pty.fd().as_raw_fd(),
+                glib::IOCondition::OUT,
+                glib::clone!(@weak self as terminal, @weak pty => @default-return glib::ControlFlow::Break, move |_, _| {
+                    terminal.flush_input(&pty)
+                }),
+            );
+        }
+    }
+
+    /// Scroll up to the closest prompt above the visible area.
+    pub fn previous_prompt(&self) {
+        let adjustment = self.imp().scrolled_window.vadjustment();
+        let top = adjustment.value().ceil() as i64;
+        if let Some(row) = self.imp().blocks.borrow().previous_prompt(top) {
+            adjustment.set_value(row as f64);
+        }
+    }
+
+    /// Scroll down to the closest prompt below the top of the visible area.
+    pub fn next_prompt(&self) {
+        let adjustment = self.imp().scrolled_window.vadjustment();
+        let top = adjustment.value().floor() as i64;
+        match self.imp().blocks.borrow().next_prompt(top) {
+            Some(row) => adjustment.set_value(row as f64),
+            None => adjustment.set_value(adjustment.upper() - adjustment.page_size()),
+        }
+    }
+
+    /// Enter or leave the copy mode, in which the keyboard moves a cursor
+    /// through the scrollback to select and copy text.
+    pub fn toggle_copy_mode(&self) {
+        let imp = self.imp();
+        if imp.copy_mode.borrow().is_some() {
+            self.exit_copy_mode();
+            return;
+        }
+
+        tracing::debug!("Enter copy mode.");
+        let (column, row) = imp.terminal.cursor_position();
+        let cursor = TerminalBuffer(self).char_position(Position { row, column });
+        imp.copy_mode.replace(Some(CopyMode::new(cursor)));
+        imp.terminal.unselect_all();
+        self.update_copy_mode();
+    }
+
+    fn exit_copy_mode(&self) {
+        tracing::debug!("Leave copy mode.");
+        self.imp().copy_mode.take();
+        self.update_copy_mode();
+    }
+
+    /// Handle a key press in copy mode. Keys with both Ctrl and Shift or Alt
+    /// are left to the shortcuts, all other keys are kept from the shell.
+    fn copy_mode_key(&self, key: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+        if self.imp().copy_mode.borrow().is_none() {
+            return glib::Propagation::Proceed;
+        }
+        let control = state.contains(gdk::ModifierType::CONTROL_MASK);
+        if state.contains(gdk::ModifierType::ALT_MASK)
+            || (control && state.contains(gdk::ModifierType::SHIFT_MASK))
+        {
+            return glib::Propagation::Proceed;
+        }
+
+        let input = match key {
+            gdk::Key::Escape => Some(Input::Escape),
+            gdk::Key::Return | gdk::Key::KP_Enter => Some(Input::Enter),
+            gdk::Key::BackSpace => Some(Input::Backspace),
+            gdk::Key::Left => Some(Input::Left),
+            gdk::Key::Right => Some(Input::Right),
+            gdk::Key::Up => Some(Input::Up),
+            gdk::Key::Down => Some(Input::Down),
+            _ => key.to_unicode().map(|c| {
+                if control {
+                    Input::Ctrl(c.to_ascii_lowercase())
+                } else {
+                    Input::Char(c)
+                }
+            }),
+        };
+        let Some(input) = input else {
+            return glib::Propagation::Stop;
+        };
+
+        let outcome = match self.imp().copy_mode.borrow_mut().as_mut() {
+            Some(copy_mode) => copy_mode.handle(input, &TerminalBuffer(self)),
+            None => Outcome::Exit,
+        };
+        match outcome {
+            Outcome::Continue => self.update_copy_mode(),
+            Outcome::Yank(text) => {
+                tracing::debug!("Copy selection of copy mode to clipboard.");
+                self.clipboard().set_text(&text);
+                self.exit_copy_mode();
+            }
+            Outcome::Exit => self.exit_copy_mode(),
+        }
+        glib::Propagation::Stop
+    }
+
+    /// Show the state of the copy mode and keep its cursor visible.
+    fn update_copy_mode(&self) {
+        let imp = self.imp();
+        let copy_mode = imp.copy_mode.borrow();
+        imp.copy_status.set_visible(copy_mode.is_some());
+        if let Some(copy_mode) = copy_mode.as_ref() {
+            imp.copy_status.set_label(&copy_mode.status());
+
+            let adjustment = imp.scrolled_window.vadjustment();
+            let rows = imp.terminal.row_count() as f64;
+            let row = copy_mode.cursor.row as f64;
+            if row < adjustment.value() {
+                adjustment.set_value(row);
+            } else if row >= adjustment.value() + rows {
+                adjustment.set_value(row - rows + 1.0);
+            }
+        }
+        imp.copy_overlay.queue_draw();
+    }
+
+    /// Enter or leave the hint mode, which labels the URLs, paths, hashes,
+    /// IP addresses and matches of custom patterns on the screen. Typing a
+    /// label picks its match.
+    pub fn toggle_hints(&self) {
+        let imp = self.imp();
+        if !imp.hints.borrow().is_empty() {
+            self.exit_hints();
+            return;
+        }
+        if imp.copy_mode.borrow().is_some() {
+            self.exit_copy_mode();
+        }
+
+        let buffer = TerminalBuffer(self);
+        let (top, height) = copy_mode::Buffer::screen(&buffer);
+        let lines: Vec<(i64, String)> = (top..top + height)
+            .map(|row| (row, copy_mode::Buffer::line(&buffer, row)))
+            .collect();
+        let found = hints::find(&lines, &imp.hint_patterns.borrow());
+        let labels = hints::labels(found.len(), &imp.config.borrow().hints.alphabet);
+        if labels.is_empty() {
+            tracing::debug!("No hints found on the screen.");
+            return;
+        }
+
+        tracing::debug!("Enter hint mode with {} hints.", labels.len());
+        imp.hints.replace(labels.into_iter().zip(found).collect());
+        imp.hint_input.borrow_mut().clear();
+        self.update_hints();
+    }
+
+    fn exit_hints(&self) {
+        tracing::debug!("Leave hint mode.");
+        self.imp().hints.borrow_mut().clear();
+        self.update_hints();
+    }
+
+    /// Handle a key press in hint mode. A label typed in upper case inserts
+    /// the match instead of running the configured action.
+    fn hint_key(&self, key: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+        let imp = self.imp();
+        if imp.hints.borrow().is_empty() {
+            return glib::Propagation::Proceed;
+        }
+        if state.intersects(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::ALT_MASK) {
+            return glib::Propagation::Proceed;
+        }
+
+        match key {
+            gdk::Key::Escape => {
+                self.exit_hints();
+                return glib::Propagation::Stop;
+            }
+            gdk::Key::BackSpace => {
+                imp.hint_input.borrow_mut().pop();
+            }
+            _ => {
+                let Some(c) = key.to_unicode() else {
+                    return glib::Propagation::Stop;
+                };
+                let mut input = imp.hint_input.borrow_mut();
+                input.push(c.to_ascii_lowercase());
+                let input = input.clone();
+                let hints = imp.hints.borrow();
+                let picked = hints.iter().find(|(label, _)| *label == input).cloned();
+                let matching = hints.iter().any(|(label, _)| label.starts_with(&input));
+                drop(hints);
+
+                if let Some((_, hint)) = picked {
+                    self.exit_hints();
+                    let action = if c.is_uppercase() {
+                        HintAction::Insert
+                    } else {
+                        hint.action.unwrap_or(imp.config.borrow().hints.action)
+                    };
+                    self.run_hint(&hint, action);
+                    return glib::Propagation::Stop;
+                }
+                if !matching {
+                    imp.hint_input.borrow_mut().pop();
+                }
+            }
+        }
+        self.update_hints();
+        glib::Propagation::Stop
+    }
+
+    fn run_hint(&self, hint: &Hint, action: HintAction) {
+        tracing::debug!("Run {action:?} on hint {:?}.", hint.text);
+        match action {
+            HintAction::Copy => self.clipboard().set_text(&hint.text),
+            HintAction::Open => self.open(&hint.text),
+            HintAction::Insert => self.imp().terminal.paste_text(&hint.text),
+        }
+    }
+
+    /// Open a URI or a file with the default application. Relative paths are
+    /// resolved against the current directory of the shell. Text that is
+    /// neither is copied to the clipboard instead.
+    fn open(&self, text: &str) {
+        let window = self.root().and_downcast::<gtk::Window>();
+        if glib::Uri::peek_scheme(text).is_some() {
+            gtk::UriLauncher::new(text).launch(
+                window.as_ref(),
+                None::<&gio::Cancellable>,
+                |result| {
+                    if let Err(error) = result {
+                        tracing::warn!("Failed to open URI: {error}.");
+                    }
+                },
+            );
+            return;
+        }
+
+        let path = match text.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().map(|home| home.join(rest)),
+            None if Path::new(text).is_absolute() => Some(PathBuf::from(text)),
+            None => self
+                .current_directory()
+                .map(|directory| directory.join(text)),
+        };
+        match path.filter(|path| path.exists()) {
+            Some(path) => gtk::FileLauncher::new(Some(&gio::File::for_path(path))).launch(
+                window.as_ref(),
+                None::<&gio::Cancellable>,
+                |result| {
+                    if let Err(error) = result {
+                        tracing::warn!("Failed to open file: {error}.");
+                    }
+                },
+            ),
+            None => {
+                tracing::debug!("Copy {text:?} because it cannot be opened.");
+                self.clipboard().set_text(text);
+            }
+        }
+    }
+
+    /// The match of a hint pattern at a point of the VTE widget.
+    fn match_at(&self, x: f64, y: f64) -> Option<String> {
+        let (text, _) = self.imp().terminal.check_match_at(x, y);
+        text.map(String::from)
+    }
+
+    /// Show the context menu at a point of the VTE widget, with the actions
+    /// for the link at that point if there is one.
+    fn show_context_menu(&self, x: f64, y: f64) {
+        let imp = self.imp();
+        let Some(menu) = imp.context_menu.get() else {
+            return;
+        };
+        let link = self.match_at(x, y);
+        self.action_set_enabled("terminal.open-link", link.is_some());
+        self.action_set_enabled("terminal.copy-link", link.is_some());
+        imp.link.replace(link);
+
+        // Tabs and splits do not exist, so new windows take their place.
+        let sections: &[&[&str]] = &[
+            &["terminal.copy", "terminal.paste", "terminal.select-all"],
+            &["terminal.open-link", "terminal.copy-link"],
+            &["terminal.search"],
+            &["app.new-window"],
+            &["terminal.zoom-reset"],
+        ];
+        menu.set_menu_model(Some(&actions::menu(
+            sections,
+            &imp.config.borrow().shortcuts,
+        )));
+
+        let point = imp
+            .terminal
+            .compute_point(self, &gtk::graphene::Point::new(x as f32, y as f32))
+            .unwrap_or_else(|| gtk::graphene::Point::new(x as f32, y as f32));
+        menu.set_pointing_to(Some(&gdk::Rectangle::new(
+            point.x() as i32,
+            point.y() as i32,
+            1,
+            1,
+        )));
+        menu.popup();
+    }
+
+    /// Insert dropped files or text. Files are inserted as quoted paths,
+    /// or as URIs if they are not local. A single directory may be entered
+    /// instead.
+    fn drop_value(&self, value: &glib::Value) -> bool {
+        if let Ok(files) = value.get::<gdk::FileList>() {
+            let config = self.imp().config.borrow().drag_and_drop.clone();
+            self.drop_files(&files.files(), &config);
+            true
+        } else if let Ok(text) = value.get::<String>() {
+            self.paste(&text);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn drop_files(&self, files: &[gio::File], config: &DragAndDrop) {
+        let directory = match files {
+            [file] if config.cd_into_directory => file.path().filter(|path| path.is_dir()),
+            _ => None,
+        };
+        // Anywhere but at an empty prompt the command would end up in typed
+        // text or in a running program, so the path is inserted instead.
+        let directory = directory
+            .and_then(|path| path.to_str().map(String::from))
+            .filter(|_| self.at_empty_prompt());
+        if let Some(path) = directory {
+            tracing::debug!("Change to dropped directory {path:?}.");
+            let path = quote::quote(&path, config.quoting);
+            self.write_child(format!("cd {path}\r").as_bytes());
+            return;
+        }
+
+        // Paths that are not valid UTF-8 are inserted as URIs, so no part of
+        // them is lost.
+        let mut words: Vec<String> = files
+            .iter()
+            .map(|file| match file.path() {
+                Some(path) => quote::quote_path(&path, &file.uri(), config.quoting),
+                None => quote::quote(&file.uri(), config.quoting),
+            })
+            .collect();
+        words.push(String::new());
+        tracing::debug!("Insert {} dropped files.", files.len());
+        self.imp().terminal.paste_text(&words.join(" "));
+    }
+
+    /// Whether the shell waits at its prompt with nothing typed, as far as
+    /// the semantic prompt marks of the shell integration tell.
+    fn at_empty_prompt(&self) -> bool {
+        let imp = self.imp();
+        let (column, row) = imp.terminal.cursor_position();
+        let cursor = Position { row, column };
+        let waiting = imp
+            .blocks
+            .borrow()
+            .last()
+            .is_some_and(|block| block.output.is_none() && block.input == Some(cursor));
+        let end = Position {
+            row,
+            column: imp.terminal.column_count(),
+        };
+        waiting
+            && self
+                .text_range(cursor, end)
+                .is_some_and(|text| text.trim().is_empty())
+    }
+
+    /// Enter copy mode and start searching the scrollback.
+    pub fn search(&self) {
+        if self.imp().copy_mode.borrow().is_none() {
+            self.toggle_copy_mode();
+        }
+        if let Some(copy_mode) = self.imp().copy_mode.borrow_mut().as_mut() {
+            copy_mode.handle(Input::Char('/'), &TerminalBuffer(self));
+        }
+        self.update_copy_mode();
+    }
+
+    /// Change the font scale by `step`, staying between 0.1 and 10.
+    pub fn zoom(&self, step: f64) {
+        let terminal = self.imp().terminal.get();
+        tracing::debug!("Scale font by {step}.");
+        terminal.set_font_scale((terminal.font_scale() + step).clamp(0.1, 10.0));
+    }
+
+    pub fn reset_zoom(&self) {
+        tracing::debug!("Reset font scale.");
+        self.imp().terminal.set_font_scale(1.0);
+    }
+
+    /// Show the labels of the hints that still match the typed input.
+    fn update_hints(&self) {
+        let imp = self.imp();
+        let layer = imp.hint_layer.get();
+        while let Some(child) = layer.first_child() {
+            layer.remove(&child);
+        }
+
+        let terminal = imp.terminal.get();
+        let top = imp.scrolled_window.vadjustment().value();
+        let column_width = terminal.char_width() as f64;
+        let row_height = terminal.char_height() as f64;
+        let config = imp.config.borrow();
+        let padding = &config.window.padding;
+        let input = imp.hint_input.borrow();
+        let buffer = TerminalBuffer(self);
+        for (label, hint) in imp.hints.borrow().iter() {
+            if !label.starts_with(input.as_str()) {
+                continue;
+            }
+            let widget = gtk::Label::builder()
+                .label(&label[input.len()..])
+                .css_classes(["osd", "monospace"])
+                .build();
+            let (cell, _) = buffer.cell_span(hint.start);
+            layer.put(
+                &widget,
+                padding.horizontal as f64 + cell as f64 * column_width,
+                padding.vertical as f64 + (hint.start.row as f64 - top) * row_height,
+            );
+        }
+    }
+
+    /// Copy the selected text to the clipboard.
+    pub fn copy_selection(&self) {
+        let Some(text) = self.imp().terminal.text_selected(vte::Format::Text) else {
+            return;
+        };
+        let text = match self
+            .imp()
+            .config
+            .borrow()
+            .clipboard
+            .trim_trailing_whitespace
+        {
+            true => text
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            false => text.to_string(),
+        };
+        self.clipboard().set_text(&text);
+    }
+
+    /// Copy the selected text with its colors and attributes to the
+    /// clipboard, so it can be pasted into rich text documents.
+    pub fn copy_html(&self) {
+        if self.imp().terminal.has_selection() {
+            tracing::debug!("Copy selection as HTML to clipboard.");
+            self.imp().terminal.copy_clipboard_format(vte::Format::Html);
+        }
+    }
+
+    /// Paste the text of the clipboard or the primary selection.
+    pub fn paste_selection(&self, selection: Selection) {
+        self.selection_clipboard(selection).read_text_async(
+            None::<&gio::Cancellable>,
+            glib::clone!(@weak self as terminal => move |result| match result {
+                Ok(Some(text)) => terminal.paste(&text),
+                Ok(None) => {}
+                Err(error) => tracing::warn!("Failed to read clipboard: {error}."),
+            }),
+        );
+    }
+
+    /// Paste `text` as if it was typed. Unless the application enabled
+    /// bracketed paste, text that would run commands or contains control
+    /// characters is only pasted after confirmation.
+    pub fn paste(&self, text: &str) {
+        let config = self.imp().config.borrow().clipboard.clone();
+        let text = paste::filter(text, &config);
+        let warnings = if config.confirm_paste && !self.imp().bracketed_paste.get() {
+            paste::check(&text)
+        } else {
+            Vec::new()
+        };
+        if warnings.is_empty() {
+            self.imp().terminal.paste_text(&text);
+            return;
+        }
+
+        tracing::debug!("Confirm paste with warnings {warnings:?}.");
+        let body: Vec<&str> = warnings
+            .iter()
+            .map(|warning| warning.description())
+            .collect();
+        let dialog = adw::AlertDialog::new(Some("Paste Text?"), Some(&body.join("\n")));
+        let preview = gtk::Label::builder()
+            .label(paste::preview(&text))
+            .xalign(0.0)
+            .yalign(0.0)
+            .wrap(true)
+            .wrap_mode(gtk::pango::WrapMode::WordChar)
+            .css_classes(["monospace"])
+            .build();
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .child(&preview)
+            .max_content_height(200)
+            .propagate_natural_height(true)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .build();
+        dialog.set_extra_child(Some(&scrolled_window));
+        dialog.add_responses(&[("cancel", "_Cancel"), ("paste", "_Paste")]);
+        dialog.set_response_appearance("paste", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+        dialog.connect_response(
+            Some("paste"),
+            glib::clone!(@weak self as terminal => move |_, _| {
+                terminal.imp().terminal.paste_text(&text);
+            }),
+        );
+        dialog.present(self);
+    }
+
+    /// Copy the output of the last finished command to the clipboard.
+    pub fn copy_output(&self) {
+        let Some(block) = self.imp().blocks.borrow().last_finished().cloned() else {
+            tracing::debug!("No finished command to copy output from.");
+            return;
+        };
+        let (Some(start), Some(end)) = (block.output, block.end) else {
+            return;
+        };
+
+        if let Some(text) = self.text_range(start, end) {
+            tracing::debug!("Copy command output to clipboard.");
+            self.clipboard().set_text(&text);
+        }
+    }
+
+    /// Select the output of the last finished command in copy mode, where it
+    /// can be adjusted and copied.
+    pub fn select_output(&self) {
+        let Some(block) = self.imp().blocks.borrow().last_finished().cloned() else {
+            tracing::debug!("No finished command to select output from.");
+            return;
+        };
+        let (Some(start), Some(end)) = (block.output, block.end) else {
+            return;
+        };
+
+        // The end of the output is exclusive, the selection inclusive.
+        let buffer = TerminalBuffer(self);
+        let start = buffer.char_position(start);
+        let last = if end.column > 0 {
+            buffer.char_position(Position {
+                column: end.column - 1,
+                ..end
+            })
+        } else {
+            let length = copy_mode::Buffer::line(&buffer, end.row - 1)
+                .chars()
+                .count() as i64;
+            Position {
+                row: end.row - 1,
+                column: (length - 1).max(0),
+            }
+        };
+        if last < start {
+            tracing::debug!("The last command has no output to select.");
+            return;
+        }
+
+        tracing::debug!("Select command output in copy mode.");
+        self.imp().copy_mode.replace(Some(CopyMode::with_selection(
+            start,
+            last,
+            SelectionKind::Character,
+        )));
+        self.imp().terminal.unselect_all();
+        self.update_copy_mode();
+    }
+
+    /// Clear the lines that have scrolled off the screen.
+    pub fn clear_scrollback(&self) {
+        let terminal = self.imp().terminal.get();
+        let top = self.imp().scrolled_window.vadjustment().upper() as i64 - terminal.row_count();
+
+        // VTE has no call to only clear the history, but dropping the
+        // scrollback to zero lines discards it.
+        terminal.set_scrollback_lines(0);
+        terminal.set_scrollback_lines(self.scrollback_lines());
+        self.imp().blocks.borrow_mut().truncate_before(top);
+        self.imp().gutter.queue_draw();
+    }
+
+    /// Reset the terminal state, e.g. after a program left it garbled.
+    pub fn reset(&self) {
+        self.imp().terminal.reset(true, false);
+        self.reset_modes();
+    }
+
+    /// Reset the terminal state and clear both the screen and the scrollback.
+    pub fn reset_and_clear(&self) {
+        self.imp().terminal.reset(true, true);
+        self.reset_modes();
+        self.imp().blocks.borrow_mut().clear();
+        self.imp().gutter.queue_draw();
+    }
+
+    /// Turn the terminal into a view without a child process that ignores
+    /// keyboard input, e.g. to play back recordings.
+    pub fn set_read_only(&self) {
+        let terminal = self.imp().terminal.get();
+        terminal.set_input_enabled(false);
+        terminal.set_focusable(false);
+    }
+
+    /// Show `data` as if it was written by a child process.
+    pub fn feed(&self, data: &[u8]) {
+        self.imp().terminal.feed(data);
+    }
+
+    pub fn set_grid_size(&self, columns: u32, rows: u32) {
+        self.imp().terminal.set_size(columns.into(), rows.into());
+    }
+
+    /// Write the screen and the scrollback to `path` in `format`.
+    pub fn export(&self, path: &Path, format: Format) -> Result<(), glib::Error> {
+        tracing::info!("Export scrollback as {format:?} to {path:?}.");
+        let terminal = self.imp().terminal.get();
+        let file = gio::File::for_path(path);
+
+        if format == Format::Text {
+            let stream = file.replace(
+                None,
+                false,
+                gio::FileCreateFlags::NONE,
+                gio::Cancellable::NONE,
+            )?;
+            terminal.write_contents_sync(
+                &stream,
+                vte::WriteFlags::Default,
+                gio::Cancellable::NONE,
+            )?;
+            return stream.close(gio::Cancellable::NONE);
+        }
+
+        let adjustment = self.imp().scrolled_window.vadjustment();
+        let (html, _) = terminal.text_range_format(
+            vte::Format::Html,
+            adjustment.lower() as i64,
+            0,
+            adjustment.upper() as i64 - 1,
+            terminal.column_count(),
+        );
+        let html = html.unwrap_or_default();
+        let contents = match format {
+            Format::Ansi => export::ansi(&html),
+            _ => export::html(&html, &self.imp().theme.borrow()),
+        };
+        file.replace_contents(
+            contents.as_bytes(),
+            None,
+            false,
+            gio::FileCreateFlags::NONE,
+            gio::Cancellable::NONE,
+        )?;
+        Ok(())
+    }
+
+    /// Ask for a file to export the scrollback to. The format is picked from
+    /// the file extension, or else from the chosen filter, whose extension is
+    /// then added to the file name.
+    // FIXME GtkFileDialog does not tell which filter was chosen, so the
+    // deprecated GtkFileChooserNative is used until it does.
+    #[allow(deprecated)]
+    pub fn export_dialog(&self) {
+        let window = self.root().and_downcast::<gtk::Window>();
+        let dialog = gtk::FileChooserNative::new(
+            Some("Export Scrollback"),
+            window.as_ref(),
+            gtk::FileChooserAction::Save,
+            Some("_Export"),
+            None,
+        );
+        dialog.set_modal(true);
+        dialog.set_current_name("scrollback");
+        for format in Format::ALL {
+            let filter = gtk::FileFilter::new();
+            filter.set_name(Some(format.name()));
+            filter.add_suffix(format.extension());
+            dialog.add_filter(&filter);
+        }
+
+        dialog.connect_response(
+            glib::clone!(@weak self as terminal => move |dialog, response| {
+                terminal.imp().export_dialog.take();
+                if response != gtk::ResponseType::Accept {
+                    return;
+                }
+                let Some(mut path) = dialog.file().and_then(|file| file.path()) else {
+                    return;
+                };
+                let format = match Format::from_path(&path) {
+                    Some(format) => format,
+                    None => {
+                        let name = dialog.filter().and_then(|filter| filter.name());
+                        let format = Format::ALL
+                            .into_iter()
+                            .find(|format| name.as_deref() == Some(format.name()))
+                            .unwrap_or(Format::Text);
+                        path.as_mut_os_string().push(format!(".{}", format.extension()));
+                        format
+                    }
+                };
+                if let Err(error) = terminal.export(&path, format) {
+                    tracing::error!("Failed to export scrollback: {error}.");
+                    let dialog = adw::AlertDialog::new(
+                        Some("Export Failed"),
+                        Some(&format!(
+                            "The scrollback could not be written to {}.\n\n{error}",
+                            path.display()
+                        )),
+                    );
+                    dialog.add_response("close", "_Close");
+                    dialog.present(&terminal);
+                }
+            }),
+        );
+        dialog.show();
+        // GTK does not keep native dialogs alive while they are shown.
+        self.imp().export_dialog.replace(Some(dialog));
+    }
+
+    /// Start recording the session to `path` in the asciicast v2 format.
+    pub fn start_recording(&self, path: &Path) -> io::Result<()> {
+        let terminal = self.imp().terminal.get();
+        let recorder = Recorder::create(
+            path,
+            terminal.column_count() as u32,
+            terminal.row_count() as u32,
+            self.title().as_deref(),
+        )?;
+        self.imp().recorder.replace(Some(recorder));
+        self.emit_by_name::<()>("recording-changed", &[&true]);
+        Ok(())
+    }
+
+    pub fn stop_recording(&self) {
+        if self.imp().recorder.take().is_some() {
+            tracing::info!("Stop recording.");
+            self.emit_by_name::<()>("recording-changed", &[&false]);
+        }
+    }
+
+    /// Stop the running recording or ask for a file to start a new one.
+    pub fn toggle_recording(&self) {
+        if self.imp().recorder.borrow().is_some() {
+            self.stop_recording();
+            return;
+        }
+
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Asciicast"));
+        filter.add_pattern("*.cast");
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+
+        let dialog = gtk::FileDialog::builder()
+            .title("Record Session")
+            .initial_name("session.cast")
+            .filters(&filters)
+            .modal(true)
+            .build();
+        let window = self.root().and_downcast::<gtk::Window>();
+        dialog.save(
+            window.as_ref(),
+            gio::Cancellable::NONE,
+            glib::clone!(@weak self as terminal => move |result| {
+                // The dialog also fails when it is dismissed.
+                let Some(path) = result.ok().and_then(|file| file.path()) else {
+                    return;
+                };
+                if let Err(error) = terminal.start_recording(&path) {
+                    tracing::error!("Failed to start recording to {path:?}: {error}.");
+                }
+            }),
+        );
+    }
+
+    /// Write an event to the running recording. The recording is stopped if
+    /// writing fails.
+    fn record<F: FnOnce(&mut Recorder) -> io::Result<()>>(&self, event: F) {
+        let mut recorder = self.imp().recorder.borrow_mut();
+        let Some(result) = recorder.as_mut().map(event) else {
+            return;
+        };
+        drop(recorder);
+
+        if let Err(error) = result {
+            tracing::error!("Failed to write recording: {error}.");
+            self.stop_recording();
+        }
+    }
+
+    fn scrollback_lines(&self) -> std::ffi::c_long {
+        match self.imp().config.borrow().scrollback.lines {
+            ScrollbackLines::Limited(lines) => lines.into(),
+            ScrollbackLines::Unlimited(_) => -1,
+        }
+    }
+
+    fn text_range(&self, start: Position, end: Position) -> Option<String> {
+        let (text, _) = self.imp().terminal.text_range_format(
+            vte::Format::Text,
+            start.row,
+            start.column,
+            end.row,
+            end.column,
+        );
+        text.map(|text| text.trim_end_matches('\n').to_string())
+    }
+
+    fn read_child(&self, pty: &vte::Pty) -> io::Result<usize> {
+        let mut buffer = [0; 64 * 1024];
+        let length = unsafe {
+            libc::read(
+                pty.fd().as_raw_fd(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+        if length < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let length = length as usize;
+        self.receive(&buffer[..length]);
+        Ok(length)
+    }
+
+    fn flush_input(&self, pty: &vte::Pty) -> glib::ControlFlow {
+        let mut input = self.imp().input.borrow_mut();
+        while !input.is_empty() {
+            let length =
+                unsafe { libc::write(pty.fd().as_raw_fd(), input.as_ptr().cast(), input.len()) };
+            if length < 0 {
+                let error = io::Error::last_os_error();
+                match error.kind() {
+                    io::ErrorKind::Interrupted => continue,
+                    io::ErrorKind::WouldBlock => return glib::ControlFlow::Continue,
+                    _ => {
+                        tracing::warn!("Failed to write to child process: {error}.");
+                        input.clear();
+                        return glib::ControlFlow::Break;
+                    }
+                }
+            }
+            input.drain(..length as usize);
+        }
+        glib::ControlFlow::Break
+    }
+
+    fn receive(&self, data: &[u8]) {
+        self.record(|recorder| recorder.output(data));
+        let sequences = self.imp().scanner.borrow_mut().scan(data);
+
+        let mut output = self.imp().output.borrow_mut();
+        let mut start = 0;
+        for (end, sequence) in sequences {
+            output.push_back(Output::Data(data[start..end].to_vec()));
+            output.push_back(Output::Sequence(sequence));
+            start = end;
+        }
+        if start < data.len() {
+            output.push_back(Output::Data(data[start..].to_vec()));
+        }
+        drop(output);
+
+        self.drain();
+    }
+
+    /// Feed queued output to VTE. VTE processes fed output asynchronously, so
+    /// sequences wait until the output in front of them has been processed
+    /// and the cursor position refers to their location.
+    fn drain(&self) {
+        let imp = self.imp();
+        loop {
+            let output = imp.output.borrow_mut().pop_front();
+            match output {
+                Some(Output::Data(data)) => {
+                    imp.terminal.feed(&data);
+                    imp.processing.set(true);
+                }
+                Some(Output::Sequence(sequence @ Sequence::SemanticPrompt(_)))
+                    if imp.processing.get() =>
+                {
+                    imp.output
+                        .borrow_mut()
+                        .push_front(Output::Sequence(sequence));
+                    self.wait_for_processing();
+                    break;
+                }
+                Some(Output::Sequence(sequence)) => self.handle_sequence(sequence),
+                None => break,
+            }
+        }
+    }
+
+    fn wait_for_processing(&self) {
+        if self.imp().processing_timeout.borrow().is_some() {
+            return;
+        }
+
+        let source = glib::timeout_add_local_once(
+            PROCESSING_TIMEOUT,
+            glib::clone!(@weak self as terminal => move || {
+                terminal.imp().processing_timeout.take();
+                terminal.processed();
+            }),
+        );
+        self.imp().processing_timeout.replace(Some(source));
+    }
+
+    fn processed(&self) {
+        self.imp().processing.set(false);
+        self.drain();
+    }
+
+    fn handle_sequence(&self, sequence: Sequence) {
+        match sequence {
+            Sequence::SemanticPrompt(mark) => {
+                let (column, row) = self.imp().terminal.cursor_position();
+                let position = Position { row, column };
+
+                let mut blocks = self.imp().blocks.borrow_mut();
+                blocks.mark(mark, position);
+                let finished = match (mark, blocks.last_mut()) {
+                    (Mark::CommandExecuted, Some(block)) => {
+                        block.command = block
+                            .input
+                            .and_then(|input| self.text_range(input, position))
+                            .map(|command| command.trim().to_string())
+                            .filter(|command| !command.is_empty());
+                        None
+                    }
+                    (Mark::CommandFinished(_), Some(block)) if block.end == Some(position) => {
+                        Some(block.clone())
+                    }
+                    _ => None,
+                };
+                drop(blocks);
+
+                if let Some(block) = finished {
+                    self.command_finished(&block);
+                }
+                self.imp().gutter.queue_draw();
+            }
+            Sequence::CursorStyle(style) => {
+                if style != 0 && !self.imp().config.borrow().cursor.application_shape {
+                    tracing::debug!("Ignore cursor style {style} requested by application.");
+                    self.reset_cursor_style();
+                }
+            }
+            Sequence::PrivateModes { modes, enabled } => {
+                for mode in modes {
+                    match mode {
+                        osc::BRACKETED_PASTE => self.imp().bracketed_paste.set(enabled),
+                        _ => self.imp().mouse_tracking.set(enabled),
+                    }
+                }
+            }
+            Sequence::SetClipboard { selections, text } => self.write_selections(selections, text),
+            Sequence::QueryClipboard(selection) => self.read_selection(selection),
+            Sequence::Notification { title, body } => {
+                if self.imp().config.borrow().notifications.sequences {
+                    self.notify(title.as_deref(), &body);
+                }
+            }
+        }
+    }
+
+    /// Forget the modes set by the application, as VTE does on a reset.
+    fn reset_modes(&self) {
+        self.imp().bracketed_paste.set(false);
+        self.imp().mouse_tracking.set(false);
+    }
+
+    /// Set the selections as requested by an application with OSC 52.
+    fn write_selections(&self, selections: Vec<Selection>, text: String) {
+        let config = self.imp().config.borrow().clipboard.osc52.clone();
+        if text.len() > config.max_size {
+            tracing::warn!(
+                "Ignore clipboard write of {} bytes exceeding the limit.",
+                text.len()
+            );
+            return;
+        }
+
+        let characters = text.chars().count();
+        let write = move |terminal: &Self| {
+            for selection in &selections {
+                terminal.selection_clipboard(*selection).set_text(&text);
+            }
+        };
+        match config.write {
+            Permission::Allow => write(self),
+            Permission::Ask => self.ask_clipboard_access(
+                &format!("An application wants to copy {characters} characters to the clipboard."),
+                write,
+            ),
+            Permission::Deny => tracing::debug!("Deny clipboard write by application."),
+        }
+    }
+
+    /// Answer a query of an application for a selection with OSC 52.
+    fn read_selection(&self, selection: Selection) {
+        let reply = move |terminal: &Self| {
+            terminal.selection_clipboard(selection).read_text_async(
+                None::<&gio::Cancellable>,
+                glib::clone!(@weak terminal => move |result| {
+                    let text = match result {
+                        Ok(text) => text.map(String::from).unwrap_or_default(),
+                        Err(error) => {
+                            tracing::warn!("Failed to read clipboard: {error}.");
+                            return;
+                        }
+                    };
+                    let max_size = terminal.imp().config.borrow().clipboard.osc52.max_size;
+                    if text.len() > max_size {
+                        tracing::warn!(
+                            "Ignore clipboard read of {} bytes exceeding the limit.",
+                            text.len()
+                        );
+                        return;
+                    }
+                    terminal.write_child(osc::clipboard_reply(selection, &text).as_bytes());
+                }),
+            );
+        };
+        let permission = self.imp().config.borrow().clipboard.osc52.read;
+        match permission {
+            Permission::Allow => reply(self),
+            Permission::Ask => self.ask_clipboard_access(
+                "An application wants to read the contents of the clipboard.",
+                reply,
+            ),
+            Permission::Deny => tracing::debug!("Deny clipboard read by application."),
+        }
+    }
+
+    fn selection_clipboard(&self, selection: Selection) -> gdk::Clipboard {
+        match selection {
+            Selection::Clipboard => self.clipboard(),
+            Selection::Primary => self.primary_clipboard(),
+        }
+    }
+
+    /// Ask whether to allow an access to the clipboard. Accesses while the
+    /// user is asked are denied, so applications can not pile up dialogs.
+    fn ask_clipboard_access<F: FnOnce(&Self) + 'static>(&self, body: &str, callback: F) {
+        if self.imp().clipboard_dialog.replace(true) {
+            tracing::debug!("Deny clipboard access while asking about another.");
+            return;
+        }
+
+        let dialog = adw::AlertDialog::new(Some("Allow Clipboard Access?"), Some(body));
+        dialog.add_responses(&[("deny", "_Deny"), ("allow", "_Allow")]);
+        dialog.set_default_response(Some("deny"));
+        dialog.set_close_response("deny");
+        let callback = Cell::new(Some(callback));
+        dialog.connect_response(
+            Some("allow"),
+            glib::clone!(@weak self as terminal => move |_, _| {
+                if let Some(callback) = callback.take() {
+                    callback(&terminal);
+                }
+            }),
+        );
+        dialog.connect_closed(glib::clone!(@weak self as terminal => move |_| {
+            terminal.imp().clipboard_dialog.set(false);
+        }));
+        dialog.present(self);
+    }
+
+    fn command_finished(&self, block: &Block) {
+        let threshold = self
+            .imp()
+            .config
+            .borrow()
+            .notifications
+            .long_command_seconds;
+        let (Some(threshold), Some(duration)) = (threshold, block.duration) else {
+            return;
+        };
+        if duration < Duration::from_secs(threshold) {
+            return;
+        }
+
+        let title = match block.exit_code {
+            Some(0) | None => "Command finished",
+            Some(_) => "Command failed",
+        };
+        let command = block.command.as_deref().unwrap_or("Command");
+        let status = block
+            .exit_code
+            .map(|code| format!(" with exit status {code}"))
+            .unwrap_or_default();
+        let body = format!(
+            "{command} finished after {}{status}.",
+            format_duration(duration)
+        );
+        self.notify(Some(title), &body);
+    }
+
+    /// Send a desktop notification unless the window is focused. Activating
+    /// the notification focuses the window again.
+    fn notify(&self, title: Option<&str>, body: &str) {
+        let Some(window) = self.root().and_downcast::<gtk::ApplicationWindow>() else {
+            return;
+        };
+        if window.is_active() {
+            tracing::debug!("Skip notification `{body}` for focused window.");
+            return;
+        }
+        let Some(application) = window.application() else {
+            return;
+        };
+
+        let title = title
+            .map(|title| title.to_string())
+            .or_else(|| window.title().map(|title| title.to_string()))
+            .unwrap_or_default();
+        tracing::debug!("Send notification `{title}`: `{body}`.");
+        let notification = gio::Notification::new(&title);
+        notification.set_body(Some(body));
+        notification.set_default_action_and_target_value(
+            "app.focus-window",
+            Some(&window.id().to_variant()),
+        );
+        application.send_notification(Some(&format!("window-{}", window.id())), &notification);
+    }
+
+    fn ring_bell(&self) {
+        let imp = self.imp();
+        let bell = imp.config.borrow().bell.clone();
+        if bell.mode == BellMode::None {
+            return;
+        }
+
+        // Limit the rate so printing binary output does not cause a flood.
+        let now = Instant::now();
+        if let Some(last_bell) = imp.last_bell.get() {
+            if now.duration_since(last_bell) < Duration::from_millis(bell.interval) {
+                return;
+            }
+        }
+        imp.last_bell.set(Some(now));
+
+        tracing::debug!("Ring bell with mode `{:?}`.", bell.mode);
+        match bell.mode {
+            BellMode::None => {}
+            BellMode::Audible => self.display().beep(),
+            BellMode::Visual => {
+                if let Some(animation) = imp.bell_animation.get() {
+                    animation.play();
+                }
+            }
+            BellMode::Urgent => {
+                // Only X11 has an urgency hint. Wayland can only request
+                // attention with an activation token, which a bell lacks.
+                let window = self.root().and_downcast::<gtk::Window>();
+                let Some(window) = window.filter(|window| !window.is_active()) else {
+                    return;
+                };
+                match window.surface().and_downcast::<gdk_x11::X11Surface>() {
+                    Some(surface) => surface.set_urgency_hint(true),
+                    None => tracing::debug!("Urgency hints are only supported on X11."),
+                }
+            }
+            BellMode::Notification => self.notify(None, "Bell"),
+        }
+    }
+
+    fn child_exited(&self, status: i32) {
+        tracing::info!("Child process exited with status {status}.");
+        self.imp().child.take();
+
+        // Read what is left of the output before reporting the exit.
+        if let Some(pty) = self.imp().pty.borrow().clone() {
+            while let Ok(length) = self.read_child(&pty) {
+                if length == 0 {
+                    break;
+                }
+            }
+        }
+
+        self.emit_by_name::<()>("child-exited", &[&status]);
+    }
+
+    fn update_size(&self) {
+        let Some(pty) = self.imp().pty.borrow().clone() else {
+            return;
+        };
+
+        let terminal = self.imp().terminal.get();
+        let size = (terminal.row_count() as i32, terminal.column_count() as i32);
+        if pty.size().ok() == Some(size) {
+            return;
+        }
+
+        tracing::debug!("Resize pseudo terminal to {size:?}.");
+        if let Err(error) = pty.set_size(size.0, size.1) {
+            tracing::warn!("Failed to resize pseudo terminal: {error}.");
+        }
+        self.record(|recorder| recorder.resize(size.1 as u32, size.0 as u32));
+    }
+
+    fn draw_bell(&self, context: &gtk::cairo::Context) {
+        // Unwrapping is safe because the theme deserializer checks for valid
+        // hex values.
+        let color = gdk::RGBA::from_str(&self.imp().theme.borrow().foreground).unwrap();
+        context.set_source_rgba(
+            color.red().into(),
+            color.green().into(),
+            color.blue().into(),
+            color.alpha().into(),
+        );
+        if let Err(error) = context.paint() {
+            tracing::warn!("Failed to draw visual bell: {error}.");
+        }
+    }
+
+    /// Draw the cursor and the selection of the copy mode.
+    fn draw_copy_mode(&self, context: &gtk::cairo::Context) {
+        let imp = self.imp();
+        let copy_mode = imp.copy_mode.borrow();
+        let Some(copy_mode) = copy_mode.as_ref() else {
+            return;
+        };
+        let terminal = imp.terminal.get();
+        let top = imp.scrolled_window.vadjustment().value();
+        let column_width = terminal.char_width() as f64;
+        let row_height = terminal.char_height() as f64;
+        let columns = terminal.column_count();
+        let config = imp.config.borrow();
+        let padding = &config.window.padding;
+        let cell = |row: i64, first: i64, last: i64| {
+            context.rectangle(
+                padding.horizontal as f64 + first as f64 * column_width,
+                padding.vertical as f64 + (row as f64 - top) * row_height,
+                (last - first + 1) as f64 * column_width,
+                row_height,
+            );
+        };
+
+        // Unwrapping is safe because the theme deserializer checks for valid
+        // hex values.
+        let color = gdk::RGBA::from_str(&imp.theme.borrow().foreground).unwrap();
+        let fill = |alpha: f64| {
+            context.set_source_rgba(
+                color.red().into(),
+                color.green().into(),
+                color.blue().into(),
+                alpha,
+            );
+            if let Err(error) = context.fill() {
+                tracing::warn!("Failed to draw copy mode: {error}.");
+            }
+        };
+
+        // The copy mode counts characters, which can take more than one cell.
+        let buffer = TerminalBuffer(self);
+        if let Some((start, end, kind)) = copy_mode.selection() {
+            for row in start.row..=end.row {
+                let (first, last) = match kind {
+                    SelectionKind::Line => (0, columns - 1),
+                    SelectionKind::Block => (
+                        buffer.cell_span(Position { row, ..start }).0,
+                        buffer.cell_span(Position { row, ..end }).1,
+                    ),
+                    SelectionKind::Character => (
+                        if row == start.row {
+                            buffer.cell_span(start).0
+                        } else {
+                            0
+                        },
+                        if row == end.row {
+                            buffer.cell_span(end).1
+                        } else {
+                            columns - 1
+                        },
+                    ),
+                };
+                cell(row, first, last);
+            }
+            fill(0.3);
+        }
+
+        let cursor = copy_mode.cursor;
+        let (first, last) = buffer.cell_span(cursor);
+        cell(cursor.row, first, last);
+        fill(0.6);
+    }
+
+    /// Draw a marker next to every command, colored by its exit status.
+    fn draw_gutter(&self, context: &gtk::cairo::Context, width: i32) {
+        let imp = self.imp();
+        let terminal = imp.terminal.get();
+        let top = imp.scrolled_window.vadjustment().value();
+        let rows = terminal.row_count() as f64;
+        let row_height = terminal.char_height() as f64;
+        let padding = imp.config.borrow().window.padding.vertical as f64;
+
+        // Unwrapping is safe because the theme deserializer checks for valid
+        // hex values.
+        let theme = imp.theme.borrow();
+        let success = gdk::RGBA::from_str(&theme.palette[2]).unwrap();
+        let failure = gdk::RGBA::from_str(&theme.palette[1]).unwrap();
+        let mut unknown = gdk::RGBA::from_str(&theme.foreground).unwrap();
+        unknown.set_alpha(0.3);
+
+        for block in imp.blocks.borrow().iter() {
+            let first = block.prompt.row as f64;
+            let last = match block.end {
+                Some(end) if end.column > 0 => end.row as f64 + 1.0,
+                Some(end) => end.row as f64,
+                None => first + 1.0,
+            };
+            let start = (first - top).max(0.0);
+            let end = (last.max(first + 1.0) - top).min(rows);
+            if end <= start {
+                continue;
+            }
+
+            let color = match block.exit_code {
+                Some(0) => &success,
+                Some(_) => &failure,
+                None => &unknown,
+            };
+            context.set_source_rgba(
+                color.red().into(),
+                color.green().into(),
+                color.blue().into(),
+                color.alpha().into(),
+            );
+            context.rectangle(
+                0.0,
+                padding + start * row_height,
+                GUTTER_MARKER_WIDTH.min(width as f64),
+                (end - start) * row_height - 1.0,
+            );
+            if let Err(error) = context.fill() {
+                tracing::warn!("Failed to draw command marker: {error}.");
+            }
+        }
+    }
+}
+
+/// The text of the terminal as seen by the copy mode.
+struct TerminalBuffer<'a>(&'a Terminal);
+
+impl copy_mode::Buffer for TerminalBuffer<'_> {
+    fn line(&self, row: i64) -> String {
+        let terminal = self.0.imp().terminal.get();
+        let (text, _) =
+            terminal.text_range_format(vte::Format::Text, row, 0, row, terminal.column_count());
+        text.map(|text| text.trim_end_matches('\n').to_string())
+            .unwrap_or_default()
+    }
+
+    fn rows(&self) -> (i64, i64) {
+        let adjustment = self.0.imp().scrolled_window.vadjustment();
+        (adjustment.lower() as i64, adjustment.upper() as i64 - 1)
+    }
+
+    fn screen(&self) -> (i64, i64) {
+        let imp = self.0.imp();
+        let top = imp.scrolled_window.vadjustment().value() as i64;
+        (top, imp.terminal.row_count())
+    }
+}
+
+/// Mapping between columns counted in characters, as the copy mode and the
+/// hints do, and columns counted in cells, as VTE does.
+impl TerminalBuffer<'_> {
+    fn cells(&self, row: i64) -> Vec<i64> {
+        let ambiguous_wide = self.0.imp().terminal.cjk_ambiguous_width() == 2;
+        copy_mode::cells(&copy_mode::Buffer::line(self, row), |c| {
+            if c.is_zero_width() {
+                0
+            } else if c.is_wide() || (ambiguous_wide && c.is_wide_cjk()) {
+                2
+            } else {
+                1
+            }
+        })
+    }
+
+    /// The first and the last cell of the character at `position`.
+    fn cell_span(&self, position: Position) -> (i64, i64) {
+        copy_mode::cell_span(&self.cells(position.row), position.column)
+    }
+
+    /// The position in characters of the cell at `position`.
+    fn char_position(&self, position: Position) -> Position {
+        let column = copy_mode::char_column(&self.cells(position.row), position.column);
+        Position { column, ..position }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, seconds) => format!("{seconds}s"),
+        (0, minutes, seconds) => format!("{minutes}m {seconds}s"),
+        (hours, minutes, seconds) => format!("{hours}h {minutes}m {seconds}s"),
+    }
 }
 
 mod imp {
     use super::*;
+    use glib::subclass::Signal;
+    use std::{
+        cell::{Cell, OnceCell, RefCell},
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            OnceLock,
+        },
+    };
+
+    static NEXT_ID: AtomicU32 = AtomicU32::new(1);
 
     #[derive(Debug, Default, gtk::CompositeTemplate)]
     #[template(string = "
@@ -102,8 +1932,44 @@ mod imp {
         using Adw 1;
         using Vte 3.91;
         template $GalacticTerminal : Adw.Bin {
-            Gtk.ScrolledWindow scrolled_window {
-                Vte.Terminal terminal {}
+            Gtk.Overlay {
+                Gtk.ScrolledWindow scrolled_window {
+                    Vte.Terminal terminal {}
+                }
+
+                [overlay]
+                Gtk.DrawingArea gutter {
+                    can-target: false;
+                    halign: start;
+                    width-request: 3;
+                }
+
+                [overlay]
+                Gtk.DrawingArea flash {
+                    can-target: false;
+                    opacity: 0;
+                }
+
+                [overlay]
+                Gtk.DrawingArea copy_overlay {
+                    can-target: false;
+                }
+
+                [overlay]
+                Gtk.Fixed hint_layer {
+                    can-target: false;
+                }
+
+                [overlay]
+                Gtk.Label copy_status {
+                    can-target: false;
+                    visible: false;
+                    halign: end;
+                    valign: start;
+                    margin-top: 6;
+                    margin-end: 18;
+                    styles ['osd', 'monospace']
+                }
             }
         }
     ")]
@@ -113,6 +1979,56 @@ mod imp {
 
         #[template_child]
         pub terminal: TemplateChild<vte::Terminal>,
+
+        #[template_child]
+        pub gutter: TemplateChild<gtk::DrawingArea>,
+
+        #[template_child]
+        pub flash: TemplateChild<gtk::DrawingArea>,
+
+        #[template_child]
+        pub copy_overlay: TemplateChild<gtk::DrawingArea>,
+
+        #[template_child]
+        pub hint_layer: TemplateChild<gtk::Fixed>,
+
+        #[template_child]
+        pub copy_status: TemplateChild<gtk::Label>,
+
+        pub config: RefCell<Config>,
+        pub theme: RefCell<Theme>,
+        pub css_provider: gtk::CssProvider,
+
+        pub pty: RefCell<Option<vte::Pty>>,
+        pub child: Cell<Option<glib::Pid>>,
+        pub command: RefCell<Vec<String>>,
+        pub spawned_at: Cell<Option<Instant>>,
+        pub bracketed_paste: Cell<bool>,
+        pub mouse_tracking: Cell<bool>,
+        pub respawn_attempts: Cell<u32>,
+        pub respawn_deadline: Cell<Option<Instant>>,
+        pub respawn_timer: RefCell<Option<glib::SourceId>>,
+        pub blink_timer: RefCell<Option<glib::SourceId>>,
+        #[allow(deprecated)]
+        pub export_dialog: RefCell<Option<gtk::FileChooserNative>>,
+        pub input: RefCell<Vec<u8>>,
+        pub output: RefCell<VecDeque<Output>>,
+        pub scanner: RefCell<Scanner>,
+        pub processing: Cell<bool>,
+        pub processing_timeout: RefCell<Option<glib::SourceId>>,
+        pub blocks: RefCell<Blocks>,
+        pub last_bell: Cell<Option<Instant>>,
+        pub clipboard_dialog: Cell<bool>,
+        pub bell_animation: OnceCell<adw::TimedAnimation>,
+        pub shortcut_controller: OnceCell<gtk::ShortcutController>,
+        pub copy_mode: RefCell<Option<CopyMode>>,
+        pub hint_patterns: RefCell<Vec<Pattern>>,
+        pub hints: RefCell<Vec<(String, Hint)>>,
+        pub hint_input: RefCell<String>,
+        pub context_menu: OnceCell<gtk::PopoverMenu>,
+        pub link: RefCell<Option<String>>,
+        pub recorder: RefCell<Option<Recorder>>,
+        pub id: Cell<u32>,
     }
 
     #[glib::object_subclass]
@@ -123,6 +2039,78 @@ mod imp {
 
         fn class_init(class: &mut Self::Class) {
             class.bind_template();
+
+            class.install_action("terminal.copy", None, |terminal, _, _| {
+                tracing::debug!("Copy selection to clipboard.");
+                terminal.copy_selection();
+                terminal.imp().terminal.unselect_all();
+            });
+            class.install_action("terminal.paste", None, |terminal, _, _| {
+                tracing::debug!("Paste from clipboard.");
+                terminal.paste_selection(Selection::Clipboard);
+                terminal.imp().terminal.unselect_all();
+            });
+            class.install_action("terminal.select-all", None, |terminal, _, _| {
+                terminal.imp().terminal.select_all();
+            });
+            class.install_action("terminal.open-link", None, |terminal, _, _| {
+                if let Some(link) = terminal.imp().link.take() {
+                    terminal.open(&link);
+                }
+            });
+            class.install_action("terminal.copy-link", None, |terminal, _, _| {
+                if let Some(link) = terminal.imp().link.take() {
+                    terminal.clipboard().set_text(&link);
+                }
+            });
+            class.install_action("terminal.search", None, |terminal, _, _| {
+                terminal.search();
+            });
+            class.install_action("terminal.zoom-in", None, |terminal, _, _| {
+                terminal.zoom(0.1);
+            });
+            class.install_action("terminal.zoom-out", None, |terminal, _, _| {
+                terminal.zoom(-0.1);
+            });
+            class.install_action("terminal.zoom-reset", None, |terminal, _, _| {
+                terminal.reset_zoom();
+            });
+            class.install_action("terminal.previous-prompt", None, |terminal, _, _| {
+                terminal.previous_prompt();
+            });
+            class.install_action("terminal.next-prompt", None, |terminal, _, _| {
+                terminal.next_prompt();
+            });
+            class.install_action("terminal.select-output", None, |terminal, _, _| {
+                terminal.select_output();
+            });
+            class.install_action("terminal.copy-output", None, |terminal, _, _| {
+                terminal.copy_output();
+            });
+            class.install_action("terminal.copy-html", None, |terminal, _, _| {
+                terminal.copy_html();
+            });
+            class.install_action("terminal.copy-mode", None, |terminal, _, _| {
+                terminal.toggle_copy_mode();
+            });
+            class.install_action("terminal.hints", None, |terminal, _, _| {
+                terminal.toggle_hints();
+            });
+            class.install_action("terminal.clear-scrollback", None, |terminal, _, _| {
+                terminal.clear_scrollback();
+            });
+            class.install_action("terminal.reset", None, |terminal, _, _| {
+                terminal.reset();
+            });
+            class.install_action("terminal.reset-and-clear", None, |terminal, _, _| {
+                terminal.reset_and_clear();
+            });
+            class.install_action("terminal.export", None, |terminal, _, _| {
+                terminal.export_dialog();
+            });
+            class.install_action("terminal.toggle-recording", None, |terminal, _, _| {
+                terminal.toggle_recording();
+            });
         }
 
         fn instance_init(object: &glib::subclass::InitializingObject<Self>) {
@@ -131,63 +2119,237 @@ mod imp {
     }
 
     impl ObjectImpl for Terminal {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![
+                    Signal::builder("child-exited")
+                        .param_types([i32::static_type()])
+                        .build(),
+                    Signal::builder("recording-changed")
+                        .param_types([bool::static_type()])
+                        .build(),
+                    Signal::builder("respawn-countdown")
+                        .param_types([u32::static_type()])
+                        .build(),
+                ]
+            })
+        }
+
         fn constructed(&self) {
             self.parent_constructed();
+            let obj = self.obj();
 
-            // Set up keyboard shortcuts.
-            let event_key_controller = gtk::EventControllerKey::new();
-            let terminal_copy = self.terminal.clone();
-            event_key_controller.connect_key_pressed(move |_, key, _, modifier_type| {
-                let shortcut_mask = gdk::ModifierType::CONTROL_MASK;
-                if modifier_type.intersection(shortcut_mask).is_empty() {
-                    return glib::Propagation::Proceed;
-                }
-
-                tracing::trace!(
-                    "Pressed key with shortcut mask: `{}`",
-                    key.name().unwrap_or_default()
-                );
-
-                match key.name().unwrap_or_default().as_str() {
-                    "V" => {
-                        tracing::debug!("Paste from clipboard.");
-                        terminal_copy.emit_paste_clipboard();
-                        terminal_copy.unselect_all();
-                        glib::Propagation::Stop
-                    }
-                    "C" if terminal_copy.has_selection() => {
-                        tracing::debug!("Copy selection to clipboard.");
-                        terminal_copy.emit_copy_clipboard();
-                        terminal_copy.unselect_all();
-                        glib::Propagation::Stop
-                    }
-                    "plus" | "equal" => {
-                        tracing::debug!("Scale font up.");
-                        terminal_copy
-                            .set_font_scale(10.0_f64.min(terminal_copy.font_scale() + 0.1));
-                        glib::Propagation::Stop
-                    }
-                    "minus" | "underscore" => {
-                        tracing::debug!("Scale font down.");
-                        terminal_copy.set_font_scale(0.1_f64.max(terminal_copy.font_scale() - 0.1));
-                        glib::Propagation::Stop
+            self.id.set(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+            // Forward input to the child process.
+            self.terminal
+                .connect_commit(glib::clone!(@weak obj => move |_, text, _| {
+                    obj.restart_blink_timeout();
+                    obj.write_child(text.as_bytes());
+                }));
+
+            // Continue feeding output once VTE has processed it.
+            self.terminal
+                .connect_contents_changed(glib::clone!(@weak obj => move |_| {
+                    obj.processed();
+                }));
+            self.terminal
+                .connect_cursor_moved(glib::clone!(@weak obj => move |_| {
+                    obj.processed();
+                }));
+            self.terminal
+                .connect_char_size_changed(glib::clone!(@weak obj => move |_, _, _| {
+                    obj.update_size();
+                }));
+
+            // Draw command markers.
+            self.gutter
+                .set_draw_func(glib::clone!(@weak obj => move |_, context, width, _| {
+                    obj.draw_gutter(context, width);
+                }));
+            let gutter = self.gutter.get();
+            self.scrolled_window
+                .vadjustment()
+                .connect_value_changed(move |_| gutter.queue_draw());
+
+            // Set up custom styles.
+            // FIXME This way of applying custom CSS is deprecated and needs a replacement.
+            #[allow(deprecated)]
+            self.terminal
+                .style_context()
+                .add_provider(&self.css_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+
+            // Set up visual bell.
+            self.flash
+                .set_draw_func(glib::clone!(@weak obj => move |_, context, _, _| {
+                    obj.draw_bell(context);
+                }));
+            let bell_animation = adw::TimedAnimation::new(
+                &*self.flash,
+                0.3,
+                0.0,
+                VISUAL_BELL_DURATION,
+                adw::PropertyAnimationTarget::new(&*self.flash, "opacity"),
+            );
+            self.bell_animation.set(bell_animation).unwrap();
+            self.terminal
+                .connect_bell(glib::clone!(@weak obj => move |_| {
+                    obj.ring_bell();
+                }));
+
+            // Check pasted text, whether it is pasted with a shortcut of
+            // Galactic or of VTE.
+            self.terminal
+                .connect_paste_clipboard(glib::clone!(@weak obj => move |terminal| {
+                    terminal.stop_signal_emission_by_name("paste-clipboard");
+                    obj.paste_selection(Selection::Clipboard);
+                }));
+
+            // Copy through Galactic to apply the clipboard options, and copy
+            // right away when copying on select.
+            self.terminal
+                .connect_copy_clipboard(glib::clone!(@weak obj => move |terminal| {
+                    terminal.stop_signal_emission_by_name("copy-clipboard");
+                    obj.copy_selection();
+                }));
+            self.terminal
+                .connect_selection_changed(glib::clone!(@weak obj => move |terminal| {
+                    obj.action_set_enabled("terminal.copy", terminal.has_selection());
+                    let copy_on_select = obj.imp().config.borrow().clipboard.copy_on_select;
+                    if copy_on_select && terminal.has_selection() {
+                        obj.copy_selection();
                     }
-                    "0" => {
-                        tracing::debug!("Reset font scale.");
-                        terminal_copy.set_font_scale(1.0);
-                        glib::Propagation::Stop
+                }));
+
+            // Paste the primary selection on middle click, unless the
+            // application tracks the mouse and Shift is not held. The
+            // gesture runs before VTE, which would paste unchecked.
+            let middle_click = gtk::GestureClick::builder()
+                .button(gdk::BUTTON_MIDDLE)
+                .propagation_phase(gtk::PropagationPhase::Capture)
+                .build();
+            middle_click.connect_pressed(glib::clone!(@weak obj => move |gesture, _, _, _| {
+                let shift = gesture.current_event_state().contains(gdk::ModifierType::SHIFT_MASK);
+                if obj.imp().mouse_tracking.get() && !shift {
+                    return;
+                }
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                if obj.imp().config.borrow().clipboard.middle_click_paste {
+                    obj.paste_selection(Selection::Primary);
+                }
+            }));
+            self.terminal.add_controller(middle_click);
+
+            // Set up action shortcuts.
+            let shortcut_controller = actions::shortcut_controller();
+            obj.add_controller(shortcut_controller.clone());
+            self.shortcut_controller.set(shortcut_controller).unwrap();
+
+            // Set up copy mode. Its keys are handled before VTE sees them.
+            self.copy_overlay
+                .set_draw_func(glib::clone!(@weak obj => move |_, context, _, _| {
+                    obj.draw_copy_mode(context);
+                }));
+            let copy_overlay = self.copy_overlay.get();
+            self.scrolled_window
+                .vadjustment()
+                .connect_value_changed(move |_| copy_overlay.queue_draw());
+            let copy_mode_controller = gtk::EventControllerKey::new();
+            copy_mode_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+            copy_mode_controller.connect_key_pressed(
+                glib::clone!(@weak obj => @default-return glib::Propagation::Proceed, move |_, key, _, state| {
+                    obj.copy_mode_key(key, state)
+                }),
+            );
+            obj.add_controller(copy_mode_controller);
+
+            // Set up hint mode. The labels only fit the screen they were made
+            // for, so scrolling leaves it.
+            let hint_controller = gtk::EventControllerKey::new();
+            hint_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+            hint_controller.connect_key_pressed(
+                glib::clone!(@weak obj => @default-return glib::Propagation::Proceed, move |_, key, _, state| {
+                    obj.hint_key(key, state)
+                }),
+            );
+            obj.add_controller(hint_controller);
+            self.scrolled_window.vadjustment().connect_value_changed(
+                glib::clone!(@weak obj => move |_| {
+                    if !obj.imp().hints.borrow().is_empty() {
+                        obj.exit_hints();
                     }
-                    _ => glib::Propagation::Proceed,
+                }),
+            );
+            let match_click = gtk::GestureClick::new();
+            match_click.set_propagation_phase(gtk::PropagationPhase::Capture);
+            match_click.connect_pressed(glib::clone!(@weak obj => move |gesture, _, x, y| {
+                if !gesture
+                    .current_event_state()
+                    .contains(gdk::ModifierType::CONTROL_MASK)
+                {
+                    return;
                 }
-            });
-            self.terminal.add_controller(event_key_controller);
+                if let Some(link) = obj.match_at(x, y) {
+                    gesture.set_state(gtk::EventSequenceState::Claimed);
+                    obj.open(&link);
+                }
+            }));
+            self.terminal.add_controller(match_click);
 
-            // Disable bell sound.
+            // Accept dropped files and text.
+            let drop_target = gtk::DropTarget::new(glib::Type::INVALID, gdk::DragAction::COPY);
+            drop_target.set_types(&[gdk::FileList::static_type(), glib::Type::STRING]);
+            drop_target.connect_drop(
+                glib::clone!(@weak obj => @default-return false, move |_, value, _, _| {
+                    obj.drop_value(value)
+                }),
+            );
+            self.terminal.add_controller(drop_target);
+
+            // Set up the context menu. Like middle clicks, right clicks go to
+            // an application tracking the mouse unless Shift is held.
+            let context_menu = gtk::PopoverMenu::builder()
+                .has_arrow(false)
+                .halign(gtk::Align::Start)
+                .build();
+            context_menu.set_parent(&*obj);
+            self.context_menu.set(context_menu).unwrap();
+            let secondary_click = gtk::GestureClick::builder()
+                .button(gdk::BUTTON_SECONDARY)
+                .propagation_phase(gtk::PropagationPhase::Capture)
+                .build();
+            secondary_click.connect_pressed(glib::clone!(@weak obj => move |gesture, _, x, y| {
+                let shift = gesture.current_event_state().contains(gdk::ModifierType::SHIFT_MASK);
+                if obj.imp().mouse_tracking.get() && !shift {
+                    return;
+                }
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                obj.show_context_menu(x, y);
+            }));
+            self.terminal.add_controller(secondary_click);
+
+            // Disable bell sound. Bells are handled according to the config.
             self.terminal.set_audible_bell(false);
+
+            // Copying needs a selection, otherwise the shortcut goes to the
+            // application.
+            obj.action_set_enabled("terminal.copy", false);
+        }
+
+        fn dispose(&self) {
+            if let Some(context_menu) = self.context_menu.get() {
+                context_menu.unparent();
+            }
         }
     }
 
-    impl WidgetImpl for Terminal {}
+    impl WidgetImpl for Terminal {
+        fn size_allocate(&self, width: i32, height: i32, baseline: i32) {
+            self.parent_size_allocate(width, height, baseline);
+            self.obj().update_size();
+        }
+    }
 
     impl BinImpl for Terminal {}
 }
@@ -1,6 +1,22 @@
-use crate::{config::Config, theme::Theme};
+use crate::{
+    config::{
+        close::Close,
+        general::OnExit,
+        window::{HeaderBar, StartMode},
+        Config,
+    },
+    process::ExitStatus,
+    state::WindowState,
+    theme::Theme,
+    ui::{application::Application, palette::Palette, terminal::Terminal},
+};
 use adw::{prelude::*, subclass::prelude::*};
 use gtk::{gio, glib};
+use std::path::{Path, PathBuf};
+
+/// The distance from the top edge in pixels at which a hidden header bar is
+/// revealed in auto-hide mode.
+const HEADER_BAR_REVEAL_DISTANCE: f64 = 4.0;
 
 glib::wrapper! {
     pub struct Window(ObjectSubclass<imp::Window>)
@@ -18,44 +34,262 @@ impl Window {
 
     pub fn apply_config(&self, config: &Config) {
         tracing::debug!("Apply config `{config:?}`.");
+        self.imp().config.replace(config.clone());
 
-        // Set window size.
+        // Set window size. Drop-down windows span the width of the monitor.
+        // The size of open windows is left alone when the config is reloaded.
         self.set_size_request(600, 400);
-        self.set_default_size(
-            config.window.size.width as i32,
-            config.window.size.height as i32,
-        );
+        self.imp()
+            .dropdown_height
+            .set(config.dropdown.height.min(100));
+        if self.imp().dropdown.get() {
+            self.update_dropdown_size();
+        } else if !self.is_mapped() {
+            self.set_default_size(
+                config.window.size.width as i32,
+                config.window.size.height as i32,
+            );
+        }
+        self.imp()
+            .hide_on_focus_loss
+            .set(config.dropdown.hide_on_focus_loss);
 
-        // Configure window title.
-        self.set_title(Some(&config.window.title));
+        // Configure header bar. In auto-hide mode it is laid over the
+        // terminal, so revealing it does not resize the terminal.
+        let toolbar_view = self.imp().toolbar_view.get();
+        self.imp().close.replace(config.close.clone());
+        toolbar_view
+            .set_extend_content_to_top_edge(config.window.header_bar == HeaderBar::AutoHide);
+        toolbar_view.set_reveal_top_bars(config.window.header_bar == HeaderBar::Always);
 
-        // Set up close callback.
-        let window_clone = self.clone();
-        self.imp().terminal.connect_child_exited(move || {
-            tracing::info!("Terminal child process exited. Close window.");
-            window_clone.close();
-        });
+        // Configure window title.
+        self.imp()
+            .title_template
+            .replace(config.window.title.clone());
+        self.update_title();
 
         // Apply config for terminal.
         self.imp().terminal.apply_config(config);
     }
 
+    /// Turn the window into an undecorated drop-down window that slides in
+    /// and out instead of being closed.
+    pub fn set_dropdown(&self) {
+        self.imp().dropdown.set(true);
+        self.imp().header_bar.set_visible(false);
+        self.imp().revealer.set_reveal_child(false);
+        self.set_decorated(false);
+    }
+
+    pub fn is_dropdown(&self) -> bool {
+        self.imp().dropdown.get()
+    }
+
+    /// Size the drop-down window to the monitor it is shown on. Before the
+    /// window is shown the first monitor is used.
+    fn update_dropdown_size(&self) {
+        let display = self.display();
+        let monitor = self
+            .surface()
+            .and_then(|surface| display.monitor_at_surface(&surface))
+            .or_else(|| display.monitors().item(0).and_downcast());
+        let Some(monitor) = monitor else {
+            return;
+        };
+
+        let geometry = monitor.geometry();
+        let height = geometry.height() * self.imp().dropdown_height.get() as i32 / 100;
+        self.set_default_size(geometry.width(), height);
+    }
+
+    /// The geometry and working directory to remember for the next run.
+    pub fn window_state(&self) -> WindowState {
+        let (width, height) = self.default_size();
+        WindowState {
+            width,
+            height,
+            maximized: self.is_maximized(),
+            fullscreen: self.is_fullscreen(),
+            working_directory: self.current_directory(),
+        }
+    }
+
+    /// Restore the geometry of a previous run. Must be called before the
+    /// window is presented.
+    pub fn apply_window_state(&self, state: &WindowState) {
+        if state.width > 0 && state.height > 0 {
+            self.set_default_size(state.width, state.height);
+        }
+        self.set_maximized(state.maximized);
+        self.set_fullscreened(state.fullscreen);
+    }
+
+    /// Ask before closing the window while processes that are not ignored
+    /// run in the terminal. Returns whether the window may close right away.
+    fn confirm_close(&self) -> bool {
+        let imp = self.imp();
+        if imp.close_confirmed.get() || !imp.close.borrow().confirm {
+            return true;
+        }
+        let processes: Vec<String> = imp
+            .terminal
+            .foreground_processes()
+            .into_iter()
+            .filter(|process| !imp.close.borrow().ignore.contains(process))
+            .collect();
+        if processes.is_empty() {
+            return true;
+        }
+
+        tracing::debug!("Confirm close with running processes {processes:?}.");
+        let dialog = adw::AlertDialog::new(
+            Some("Close Window?"),
+            Some(&format!(
+                "The following processes are still running and will be terminated:\n\n{}",
+                processes.join("\n")
+            )),
+        );
+        dialog.add_responses(&[("cancel", "_Cancel"), ("close", "C_lose")]);
+        dialog.set_response_appearance("close", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+        dialog.connect_response(
+            Some("close"),
+            glib::clone!(@weak self as window => move |_, _| {
+                window.imp().close_confirmed.set(true);
+                window.close();
+            }),
+        );
+        dialog.present(self);
+        false
+    }
+
+    /// Close the window, restart the shell or keep the window open with a
+    /// bar showing the exit status, depending on the config.
+    fn child_exited(&self, status: ExitStatus) {
+        let imp = self.imp();
+        let on_exit = imp.config.borrow().general.on_exit;
+        let hold = match on_exit {
+            OnExit::Close => false,
+            OnExit::Hold => true,
+            OnExit::HoldOnError => !status.success(),
+            OnExit::Restart => {
+                imp.exit_message
+                    .replace(format!("The process exited with {status}."));
+                if imp.terminal.schedule_respawn() {
+                    tracing::info!("Terminal child process exited with {status}. Restart it.");
+                    imp.restart_button.set_visible(false);
+                    imp.cancel_button.set_visible(true);
+                    imp.toolbar_view.set_reveal_bottom_bars(true);
+                } else {
+                    let attempts = imp.terminal.respawn_attempts();
+                    self.hold(format!(
+                        "The process exited with {status}. Gave up after {attempts} restarts."
+                    ));
+                }
+                return;
+            }
+        };
+        if !hold {
+            tracing::info!("Terminal child process exited. Close window.");
+            self.close();
+            return;
+        }
+
+        tracing::info!("Terminal child process exited with {status}. Hold window.");
+        self.hold(format!("The process exited with {status}."));
+    }
+
+    /// Keep the window open with a bar offering to restart or close.
+    fn hold(&self, message: String) {
+        let imp = self.imp();
+        imp.exit_label.set_label(&message);
+        imp.exit_message.replace(message);
+        imp.restart_button.set_visible(true);
+        imp.cancel_button.set_visible(false);
+        imp.toolbar_view.set_reveal_bottom_bars(true);
+    }
+
+    pub fn restart(&self) {
+        self.imp().toolbar_view.set_reveal_bottom_bars(false);
+        self.imp().terminal.respawn();
+        self.imp().terminal.grab_focus();
+    }
+
+    /// Stop a scheduled restart and hold the window instead.
+    pub fn cancel_restart(&self) {
+        tracing::info!("Cancel restart of terminal child process.");
+        self.imp().terminal.cancel_respawn();
+        let message = self.imp().exit_message.take();
+        self.hold(message);
+    }
+
+    pub fn apply_start_mode(&self, start_mode: StartMode) {
+        match start_mode {
+            StartMode::Normal => {}
+            StartMode::Maximized => self.maximize(),
+            StartMode::Fullscreen => self.fullscreen(),
+        }
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        self.set_fullscreened(!self.is_fullscreen());
+    }
+
+    /// Show or hide the header bar. In auto-hide mode, a shown header bar is
+    /// hidden again once the pointer leaves it.
+    pub fn toggle_header_bar(&self) {
+        let toolbar_view = self.imp().toolbar_view.get();
+        toolbar_view.set_reveal_top_bars(!toolbar_view.reveals_top_bars());
+    }
+
+    /// Slide the drop-down window in, or out if it has the focus.
+    pub fn toggle_dropdown(&self) {
+        let revealer = self.imp().revealer.get();
+        if self.is_visible() && self.is_active() && revealer.reveals_child() {
+            tracing::debug!("Hide drop-down window.");
+            revealer.set_reveal_child(false);
+        } else {
+            tracing::debug!("Show drop-down window.");
+            self.present();
+            revealer.set_reveal_child(true);
+            self.imp().terminal.grab_focus();
+        }
+    }
+
+    pub fn spawn(&self, command: &[String], working_directory: Option<&Path>) {
+        self.imp().terminal.spawn(command, working_directory);
+    }
+
+    pub fn terminal(&self) -> Terminal {
+        self.imp().terminal.get()
+    }
+
+    /// Replace the configured title with a fixed one until `None` restores
+    /// it.
+    pub fn set_title_override(&self, title: Option<String>) {
+        self.imp().title_override.replace(title);
+        self.update_title();
+    }
+
+    pub fn start_recording(&self, path: &Path) {
+        if let Err(error) = self.imp().terminal.start_recording(path) {
+            tracing::error!("Failed to start recording to {path:?}: {error}.");
+        }
+    }
+
+    pub fn current_directory(&self) -> Option<PathBuf> {
+        self.imp().terminal.current_directory()
+    }
+
     pub fn apply_theme(&self, theme: &Theme) {
         tracing::debug!("Apply theme `{theme:?}`.");
 
         // Set colors for header bar.
-        // FIXME This way of applying custom CSS is deprecated and needs a replacement.
-        let header_bar = self.imp().header_bar.get();
-        let header_bar_css_provider = gtk::CssProvider::new();
-        header_bar_css_provider.load_from_string(&format!(
+        self.imp().css_provider.load_from_string(&format!(
             "headerbar {{ background-color: {}; color: {}; box-shadow: none; }}",
             theme.background, theme.foreground,
         ));
-        #[allow(deprecated)]
-        header_bar.style_context().add_provider(
-            &header_bar_css_provider,
-            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
-        );
 
         // Update color scheme.
         let style_manager = adw::StyleManager::default();
@@ -64,10 +298,37 @@ impl Window {
         // Apply theme for terminal.
         self.imp().terminal.apply_theme(theme);
     }
+
+    fn update_title(&self) {
+        if let Some(title) = self.imp().title_override.borrow().as_deref() {
+            self.set_title(Some(title));
+            return;
+        }
+
+        let terminal = self.imp().terminal.get();
+        let directory = terminal
+            .current_directory()
+            .map(|path| {
+                let home = dirs::home_dir();
+                match home.as_deref().map(|home| path.strip_prefix(home)) {
+                    Some(Ok(relative)) if relative.as_os_str().is_empty() => "~".to_string(),
+                    Some(Ok(relative)) => format!("~/{}", relative.display()),
+                    _ => path.display().to_string(),
+                }
+            })
+            .unwrap_or_default();
+        let title = self
+            .imp()
+            .title_template
+            .borrow()
+            .replace("{cwd}", &directory)
+            .replace("{title}", &terminal.title().unwrap_or_default());
+        self.set_title(Some(&title));
+    }
 }
 
 mod imp {
-    use crate::ui::terminal::Terminal;
+    use std::cell::{Cell, RefCell};
 
     use super::*;
 
@@ -77,21 +338,86 @@ mod imp {
     using Adw 1;
     using Vte 3.91;
     template $GalacticWindow : Adw.ApplicationWindow {
-        Box {
-            orientation: vertical;
-            Adw.HeaderBar header_bar {}
-            $GalacticTerminal terminal {
+        Adw.ToolbarView toolbar_view {
+            [top]
+            Adw.HeaderBar header_bar {
+                [start]
+                Image recording_indicator {
+                    icon-name: 'media-record-symbolic';
+                    tooltip-text: 'Recording';
+                    visible: false;
+                    styles ['error']
+                }
+            }
+            content: Revealer revealer {
+                transition-type: slide_down;
+                reveal-child: true;
                 vexpand: true;
+                $GalacticTerminal terminal {}
+            };
+            reveal-bottom-bars: false;
+            [bottom]
+            Box {
+                styles ['toolbar']
+                Label exit_label {
+                    hexpand: true;
+                    xalign: 0;
+                }
+                Button restart_button {
+                    label: '_Restart';
+                    use-underline: true;
+                    action-name: 'window.restart';
+                }
+                Button cancel_button {
+                    label: '_Cancel';
+                    use-underline: true;
+                    action-name: 'window.cancel-restart';
+                    visible: false;
+                }
+                Button {
+                    label: '_Close';
+                    use-underline: true;
+                    action-name: 'window.close';
+                }
             }
         }
     }
     ")]
     pub struct Window {
+        #[template_child]
+        pub toolbar_view: TemplateChild<adw::ToolbarView>,
+
         #[template_child]
         pub header_bar: TemplateChild<adw::HeaderBar>,
 
+        #[template_child]
+        pub recording_indicator: TemplateChild<gtk::Image>,
+
+        #[template_child]
+        pub revealer: TemplateChild<gtk::Revealer>,
+
+        #[template_child]
+        pub exit_label: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub restart_button: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub cancel_button: TemplateChild<gtk::Button>,
+
         #[template_child]
         pub terminal: TemplateChild<Terminal>,
+
+        pub config: RefCell<Config>,
+        pub dropdown: Cell<bool>,
+        pub dropdown_height: Cell<u32>,
+        pub hide_on_focus_loss: Cell<bool>,
+        pub close: RefCell<Close>,
+        pub close_confirmed: Cell<bool>,
+        pub exit_message: RefCell<String>,
+        pub title_template: RefCell<String>,
+        pub title_override: RefCell<Option<String>>,
+        pub css_provider: gtk::CssProvider,
     }
 
     #[glib::object_subclass]
@@ -102,6 +428,22 @@ mod imp {
 
         fn class_init(class: &mut Self::Class) {
             class.bind_template();
+
+            class.install_action("window.toggle-fullscreen", None, |window, _, _| {
+                window.toggle_fullscreen();
+            });
+            class.install_action("window.toggle-header-bar", None, |window, _, _| {
+                window.toggle_header_bar();
+            });
+            class.install_action("window.command-palette", None, |window, _, _| {
+                Palette::new(window).present(window);
+            });
+            class.install_action("window.restart", None, |window, _, _| {
+                window.restart();
+            });
+            class.install_action("window.cancel-restart", None, |window, _, _| {
+                window.cancel_restart();
+            });
         }
 
         fn instance_init(object: &glib::subclass::InitializingObject<Self>) {
@@ -112,6 +454,134 @@ mod imp {
     impl ObjectImpl for Window {
         fn constructed(&self) {
             self.parent_constructed();
+            let obj = self.obj();
+
+            // Set up custom styles.
+            // FIXME This way of applying custom CSS is deprecated and needs a replacement.
+            #[allow(deprecated)]
+            self.header_bar
+                .style_context()
+                .add_provider(&self.css_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+
+            // Ask before closing while processes are running. This handler
+            // runs first, so stopping the close also skips later handlers.
+            obj.connect_close_request(|window| {
+                if window.confirm_close() {
+                    glib::Propagation::Proceed
+                } else {
+                    glib::Propagation::Stop
+                }
+            });
+
+            // Set up exit callback.
+            let window_clone = obj.clone();
+            self.terminal.connect_child_exited(move |status| {
+                window_clone.child_exited(ExitStatus::from_wait_status(status));
+            });
+
+            // Count down to the restart of the child process.
+            let window_clone = obj.clone();
+            self.terminal.connect_respawn_countdown(move |seconds| {
+                let imp = window_clone.imp();
+                if seconds == 0 {
+                    imp.toolbar_view.set_reveal_bottom_bars(false);
+                    return;
+                }
+                let unit = if seconds == 1 { "second" } else { "seconds" };
+                imp.exit_label.set_label(&format!(
+                    "{} Restarting in {seconds} {unit}.",
+                    imp.exit_message.borrow()
+                ));
+            });
+
+            // Hide drop-down windows once they slid out, keeping the terminal
+            // alive, and slide them out when they lose the focus.
+            let window_clone = obj.clone();
+            self.revealer
+                .connect_child_revealed_notify(move |revealer| {
+                    let hidden = !revealer.reveals_child() && !revealer.is_child_revealed();
+                    if window_clone.imp().dropdown.get() && hidden {
+                        window_clone.set_visible(false);
+                    }
+                });
+            obj.connect_is_active_notify(|window| {
+                let imp = window.imp();
+                if imp.dropdown.get() && imp.hide_on_focus_loss.get() && !window.is_active() {
+                    imp.revealer.set_reveal_child(false);
+                }
+            });
+
+            // Resize drop-down windows to the monitor they are shown on.
+            obj.connect_realize(|window| {
+                let Some(surface) = window.surface() else {
+                    return;
+                };
+                surface.connect_enter_monitor(glib::clone!(@weak window => move |_, _| {
+                    if window.is_dropdown() {
+                        window.update_dropdown_size();
+                    }
+                }));
+            });
+
+            // Clear the urgency hint set by the bell once the window is
+            // focused.
+            obj.connect_is_active_notify(|window| {
+                let surface = window.surface().and_downcast::<gdk_x11::X11Surface>();
+                if let Some(surface) = surface.filter(|_| window.is_active()) {
+                    surface.set_urgency_hint(false);
+                }
+            });
+
+            // Reveal the header bar in auto-hide mode while the pointer is at
+            // the top edge or over the header bar.
+            let motion_controller = gtk::EventControllerMotion::new();
+            let window_clone = obj.clone();
+            motion_controller.connect_motion(move |_, _, y| {
+                let imp = window_clone.imp();
+                if imp.config.borrow().window.header_bar != HeaderBar::AutoHide {
+                    return;
+                }
+                let toolbar_view = imp.toolbar_view.get();
+                let distance = if toolbar_view.reveals_top_bars() {
+                    toolbar_view.top_bar_height() as f64
+                } else {
+                    HEADER_BAR_REVEAL_DISTANCE
+                };
+                toolbar_view.set_reveal_top_bars(y <= distance);
+            });
+            let window_clone = obj.clone();
+            motion_controller.connect_leave(move |_| {
+                let imp = window_clone.imp();
+                if imp.config.borrow().window.header_bar == HeaderBar::AutoHide {
+                    imp.toolbar_view.set_reveal_top_bars(false);
+                }
+            });
+            self.toolbar_view.add_controller(motion_controller);
+
+            // Show whether the session is recorded.
+            let window_clone = obj.clone();
+            self.terminal.connect_recording_changed(move |recording| {
+                window_clone
+                    .imp()
+                    .recording_indicator
+                    .set_visible(recording);
+            });
+
+            // Update title from terminal.
+            let window_clone = obj.clone();
+            self.terminal.connect_directory_changed(move || {
+                window_clone.update_title();
+                let application = window_clone.application().and_downcast::<Application>();
+                if let (Some(application), Some(directory)) =
+                    (application, window_clone.current_directory())
+                {
+                    application.remember_directory(&directory);
+                }
+            });
+            let window_clone = obj.clone();
+            self.terminal.connect_title_changed(move || {
+                window_clone.update_title();
+            });
         }
     }
 